@@ -3,10 +3,13 @@ use std::cmp::Ordering::*;
 use std::{
     error::Error,
     fs::File,
-    io::{self, BufRead, BufReader},
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    mem,
+    path::Path,
 };
 
 use clap::{App, Arg};
+use flate2::bufread::MultiGzDecoder;
 
 type MyResult<T> = Result<T, Box<dyn Error>>;
 
@@ -18,13 +21,23 @@ pub struct Config {
     show_col2: bool,
     show_col3: bool,
     insensitive: bool,
+    ignore_blanks: bool,
     delimiter: String,
+    total: bool,
+    check_order: bool,
+    zero_terminated: bool,
+    exit_status: bool,
+    strip_cr: bool,
+    unsorted: bool,
+    header: bool,
 }
 
-enum Column<'a> {
-    Col1(&'a str),
-    Col2(&'a str),
-    Col3(&'a str),
+/// 比較結果の 1 行。Col1 は file1 だけ、Col2 は file2 だけ、Col3 は両方にある行
+#[derive(Debug, PartialEq, Eq)]
+pub enum Column {
+    Col1(String),
+    Col2(String),
+    Col3(String),
 }
 
 pub fn get_args() -> MyResult<Config> {
@@ -52,6 +65,13 @@ pub fn get_args() -> MyResult<Config> {
                 .takes_value(false)
                 .help("Case-insensitive comparison of lines"),
         )
+        .arg(
+            Arg::with_name("ignore_blanks")
+                .short("b")
+                .long("ignore-leading-blanks")
+                .takes_value(false)
+                .help("Ignore leading blanks when comparing lines"),
+        )
         .arg(
             Arg::with_name("suppress_col1")
                 .short("1")
@@ -75,10 +95,62 @@ pub fn get_args() -> MyResult<Config> {
                 .short("d")
                 .long("output-delimiter")
                 .value_name("DELIM")
-                .help("Output delimiter")
+                .help("Output delimiter (may be empty)")
                 .takes_value(true)
+                .empty_values(true)
                 .default_value("\t"),
         )
+        .arg(
+            Arg::with_name("total")
+                .long("total")
+                .takes_value(false)
+                .help("Output a summary"),
+        )
+        .arg(
+            Arg::with_name("header")
+                .long("header")
+                .takes_value(false)
+                .help("Print a header line labeling the columns"),
+        )
+        .arg(
+            Arg::with_name("zero_terminated")
+                .short("z")
+                .long("zero-terminated")
+                .takes_value(false)
+                .help("Line delimiter is NUL, not newline"),
+        )
+        .arg(
+            Arg::with_name("exit_status")
+                .long("exit-status")
+                .takes_value(false)
+                .help("Exit with status 1 if the files differ"),
+        )
+        .arg(
+            Arg::with_name("strip_cr")
+                .long("strip-trailing-cr")
+                .takes_value(false)
+                .help("Strip trailing carriage return on input"),
+        )
+        .arg(
+            Arg::with_name("unsorted")
+                .long("unsorted")
+                .takes_value(false)
+                .conflicts_with("check_order")
+                .help("Align unsorted input keeping the original order (experimental)"),
+        )
+        .arg(
+            Arg::with_name("check_order")
+                .long("check-order")
+                .takes_value(false)
+                .help("Check that the input is correctly sorted"),
+        )
+        .arg(
+            Arg::with_name("nocheck_order")
+                .long("nocheck-order")
+                .takes_value(false)
+                .overrides_with("check_order")
+                .help("Do not check that the input is correctly sorted"),
+        )
         .get_matches();
 
     Ok(Config {
@@ -88,11 +160,31 @@ pub fn get_args() -> MyResult<Config> {
         show_col2: !matches.is_present("suppress_col2"),
         show_col3: !matches.is_present("suppress_col3"),
         insensitive: matches.is_present("insensitive"),
+        ignore_blanks: matches.is_present("ignore_blanks"),
         delimiter: matches.value_of("delimiter").unwrap().to_string(),
+        total: matches.is_present("total"),
+        check_order: matches.is_present("check_order"),
+        zero_terminated: matches.is_present("zero_terminated"),
+        exit_status: matches.is_present("exit_status"),
+        strip_cr: matches.is_present("strip_cr"),
+        unsorted: matches.is_present("unsorted"),
+        header: matches.is_present("header"),
     })
 }
 
-pub fn run(config: Config) -> MyResult<()> {
+/// 終了ステータスを返す。
+/// 通常は常に 0 だが、--exit-status を指定した場合は、
+/// どちらか一方にしかない行が 1 行でもあれば 1、すべての行が共通なら 0 を返す。
+/// エラーの場合は main がメッセージを表示して 1 で終了する。
+pub fn run(config: Config) -> MyResult<i32> {
+    let mut out = BufWriter::new(io::stdout().lock());
+    let code = run_with_writer(config, &mut out)?;
+    out.flush()?;
+    Ok(code)
+}
+
+/// run と同じ比較を行い、結果を out に書き込む
+pub fn run_with_writer(config: Config, out: &mut impl Write) -> MyResult<i32> {
     let file1 = &config.file1;
     let file2 = &config.file2;
 
@@ -100,33 +192,76 @@ pub fn run(config: Config) -> MyResult<()> {
         return Err(From::from("Both input files cannot be STDIN (\"-\")"));
     }
 
-    let case = |line: String| {
-        if config.insensitive {
-            line.to_lowercase()
-        } else {
-            line
+    // ディレクトリは読み始める前にエラーにする
+    for file in [file1, file2] {
+        if file != "-" && Path::new(file).is_dir() {
+            return Err(From::from(format!("commr: {}: Is a directory", file)));
         }
+    }
+
+    let case = |line: String| {
+        // CRLF のファイルと LF のファイルを同じ内容として比較できるようにする
+        let line = match line.strip_suffix('\r') {
+            Some(stripped) if config.strip_cr => stripped.to_string(),
+            _ => line,
+        };
+        keyed(line, config.insensitive, config.ignore_blanks)
+    };
+    let lines1 = read_lines(file1, open(file1)?, config.zero_terminated).map(|line| line.map(case));
+    let lines2 = read_lines(file2, open(file2)?, config.zero_terminated).map(|line| line.map(case));
+    let terminator = if config.zero_terminated { '\0' } else { '\n' };
+    let cols: Box<dyn Iterator<Item = MyResult<Column>>> = if config.unsorted {
+        Box::new(
+            align(
+                lines1.collect::<MyResult<_>>()?,
+                lines2.collect::<MyResult<_>>()?,
+            )
+            .into_iter()
+            .map(Ok),
+        )
+    } else {
+        let lines1 = check_order(lines1, 1, config.check_order);
+        let lines2 = check_order(lines2, 2, config.check_order);
+        Box::new(Comm::new(lines1, lines2))
     };
-    let mut lines1 = open(file1)?.lines().filter_map(Result::ok).map(case);
-    let mut lines2 = open(file2)?.lines().filter_map(Result::ok).map(case);
 
-    let print = |col: Column| {
+    // 表示する列だけに、ファイル名と both の見出しを付ける
+    if config.header {
+        let labels: Vec<&str> = [
+            (config.show_col1, file1.as_str()),
+            (config.show_col2, file2.as_str()),
+            (config.show_col3, "both"),
+        ]
+        .into_iter()
+        .filter_map(|(show, label)| show.then_some(label))
+        .collect();
+        if !labels.is_empty() {
+            write!(out, "{}{}", labels.join(&config.delimiter), terminator)?;
+        }
+    }
+
+    // 列の表示を抑制していても、件数はすべて数える
+    let mut counts = [0; 3];
+    let mut print = |col: Column| -> MyResult<()> {
         let mut columns = vec![];
-        match col {
+        match &col {
             Col1(val) => {
+                counts[0] += 1;
                 if config.show_col1 {
-                    columns.push(val);
+                    columns.push(val.as_str());
                 }
             }
             Col2(val) => {
+                counts[1] += 1;
                 if config.show_col2 {
                     if config.show_col1 {
                         columns.push("");
                     }
-                    columns.push(val);
+                    columns.push(val.as_str());
                 }
             }
             Col3(val) => {
+                counts[2] += 1;
                 if config.show_col3 {
                     if config.show_col1 {
                         columns.push("");
@@ -134,45 +269,37 @@ pub fn run(config: Config) -> MyResult<()> {
                     if config.show_col2 {
                         columns.push("");
                     }
-                    columns.push(val);
+                    columns.push(val.as_str());
                 }
             }
         };
 
         if !columns.is_empty() {
-            println!("{}", columns.join(&config.delimiter));
+            write!(out, "{}{}", columns.join(&config.delimiter), terminator)?;
         }
+        Ok(())
     };
 
-    let mut line1 = lines1.next();
-    let mut line2 = lines2.next();
-    while line1.is_some() || line2.is_some() {
-        match (&line1, &line2) {
-            (Some(val1), Some(val2)) => match val1.cmp(val2) {
-                Equal => {
-                    print(Column::Col3(val1));
-                    line1 = lines1.next();
-                    line2 = lines2.next();
-                }
-                Less => {
-                    print(Column::Col1(val1));
-                    line1 = lines1.next();
-                }
-                Greater => {
-                    print(Column::Col2(val2));
-                    line2 = lines2.next();
-                }
-            },
-            (Some(val1), None) => {
-                print(Column::Col1(val1));
-                line1 = lines1.next();
-            }
-            (None, Some(val2)) => {
-                print(Column::Col2(val2));
-                line2 = lines2.next();
-            }
-            _ => (),
-        }
+    for col in cols {
+        print(col?)?;
+    }
+
+    let differ = counts[0] > 0 || counts[1] > 0;
+
+    if config.total {
+        let [count1, count2, count3] = counts;
+        write!(
+            out,
+            "{}{}",
+            [
+                count1.to_string(),
+                count2.to_string(),
+                count3.to_string(),
+                "total".to_string(),
+            ]
+            .join(&config.delimiter),
+            terminator
+        )?;
     }
 
     // 自分で考えたロジック（仕様を誤解してたのでボツ）
@@ -195,14 +322,372 @@ pub fn run(config: Config) -> MyResult<()> {
     //     lines2.push(line?);
     // }
 
-    Ok(())
+    Ok(if config.exit_status && differ { 1 } else { 0 })
+}
+
+/// 2 つのソート済みの行の並びを比較し、各行がどの列に入るかを順に返す
+pub fn comm<'a>(
+    lines1: impl IntoIterator<Item = String> + 'a,
+    lines2: impl IntoIterator<Item = String> + 'a,
+    insensitive: bool,
+) -> impl Iterator<Item = Column> + 'a {
+    let lines1 = lines1
+        .into_iter()
+        .map(move |line| Ok(keyed(line, insensitive, false)));
+    let lines2 = lines2
+        .into_iter()
+        .map(move |line| Ok(keyed(line, insensitive, false)));
+    Comm::new(lines1, lines2).map_while(Result::ok)
+}
+
+// 比較には大文字小文字をそろえ、先頭の空白とタブを除いたキーを使い、表示には元の行を使う
+fn keyed(line: String, insensitive: bool, ignore_blanks: bool) -> (String, String) {
+    let key = if ignore_blanks {
+        line.trim_start_matches([' ', '\t'])
+    } else {
+        &line
+    };
+    let key = if insensitive {
+        key.to_lowercase()
+    } else {
+        key.to_string()
+    };
+    (line, key)
+}
+
+// (元の行, 比較キー) の並びを 2 つ受け取り、マージしながら列を返す
+//
+// 重複行の扱いは GNU comm と同じ。
+// 同じ行が file1 に N 行、file2 に M 行あるとき、min(N, M) 行を共通の列に出し、
+// 残りの |N - M| 行を多い方のファイルの列に出す。
+// 等しい行どうしは 1 行ずつ対にして両方を進めるので、ソート済みの入力であれば
+// 先読みは 1 行ずつで足りる。
+struct Comm<I1, I2> {
+    lines1: std::iter::Fuse<I1>,
+    lines2: std::iter::Fuse<I2>,
+    line1: Option<(String, String)>,
+    line2: Option<(String, String)>,
+}
+
+impl<I1, I2> Comm<I1, I2>
+where
+    I1: Iterator<Item = MyResult<(String, String)>>,
+    I2: Iterator<Item = MyResult<(String, String)>>,
+{
+    fn new(lines1: I1, lines2: I2) -> Self {
+        Comm {
+            lines1: lines1.fuse(),
+            lines2: lines2.fuse(),
+            line1: None,
+            line2: None,
+        }
+    }
+}
+
+impl<I1, I2> Iterator for Comm<I1, I2>
+where
+    I1: Iterator<Item = MyResult<(String, String)>>,
+    I2: Iterator<Item = MyResult<(String, String)>>,
+{
+    type Item = MyResult<Column>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.line1.is_none() {
+            match self.lines1.next() {
+                Some(Err(e)) => return Some(Err(e)),
+                Some(Ok(line)) => self.line1 = Some(line),
+                None => (),
+            }
+        }
+        if self.line2.is_none() {
+            match self.lines2.next() {
+                Some(Err(e)) => return Some(Err(e)),
+                Some(Ok(line)) => self.line2 = Some(line),
+                None => (),
+            }
+        }
+
+        let col = match (self.line1.take(), self.line2.take()) {
+            (Some((val1, key1)), Some((val2, key2))) => match key1.cmp(&key2) {
+                Equal => Col3(val1),
+                Less => {
+                    self.line2 = Some((val2, key2));
+                    Col1(val1)
+                }
+                Greater => {
+                    self.line1 = Some((val1, key1));
+                    Col2(val2)
+                }
+            },
+            (Some((val1, _)), None) => Col1(val1),
+            (None, Some((val2, _))) => Col2(val2),
+            (None, None) => return None,
+        };
+        Some(Ok(col))
+    }
+}
+
+// --unsorted の場合の比較。ソートされていない 2 つの行の並びを、
+// 元の順序を保ったまま最長共通部分列 (LCS) で対応づける
+//
+// lcs[i][j] に lines1[i..] と lines2[j..] の LCS の長さを後ろから求めておき、
+// 先頭から次のようにたどる。
+// - キーが等しければ共通の列に出して両方を進める
+// - そうでなければ、LCS が短くならない方 (同じなら file1) の行を
+//   そのファイルの列に出して進める
+// 全行を読み込み、O(N * M) のメモリを使うので大きなファイルには向かない。
+fn align(mut lines1: Vec<(String, String)>, mut lines2: Vec<(String, String)>) -> Vec<Column> {
+    let (n, m) = (lines1.len(), lines2.len());
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if lines1[i].1 == lines2[j].1 {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut cols = Vec::with_capacity(n.max(m));
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && lines1[i].1 == lines2[j].1 {
+            cols.push(Col3(mem::take(&mut lines1[i].0)));
+            i += 1;
+            j += 1;
+        } else if j < m && (i == n || lcs[i + 1][j] < lcs[i][j + 1]) {
+            cols.push(Col2(mem::take(&mut lines2[j].0)));
+            j += 1;
+        } else {
+            cols.push(Col1(mem::take(&mut lines1[i].0)));
+            i += 1;
+        }
+    }
+    cols
+}
+
+// -z の場合は改行ではなく NUL で区切る
+// UTF-8 として不正な行があってもそこで読むのをやめないよう、バイト列として区切ってから
+// 不正なバイトを置換文字にする
+// 読み込みや gzip の展開のエラーは、ファイルの終わりとして扱わずにそのまま返す
+fn read_lines<'a>(
+    filename: &'a str,
+    file: Box<dyn BufRead>,
+    zero_terminated: bool,
+) -> impl Iterator<Item = MyResult<String>> + 'a {
+    let (separator, strip_cr) = if zero_terminated {
+        (b'\0', false)
+    } else {
+        // BufRead::lines と同じく、CRLF の CR も取り除く
+        (b'\n', true)
+    };
+    file.split(separator).map(move |line| {
+        let mut line = line.map_err(|e| format!("{}: {}", filename, e))?;
+        if strip_cr && line.last() == Some(&b'\r') {
+            line.pop();
+        }
+        Ok(String::from_utf8_lossy(&line).into_owned())
+    })
+}
+
+// 直前の行より小さいキーの行が現れたらエラーにする
+// 読み込みのエラーはそのまま返す
+fn check_order(
+    lines: impl Iterator<Item = MyResult<(String, String)>>,
+    file_num: usize,
+    enabled: bool,
+) -> impl Iterator<Item = MyResult<(String, String)>> {
+    let mut prev: Option<String> = None;
+    lines.enumerate().map(move |(i, line)| {
+        let line = line?;
+        if enabled {
+            if prev.as_ref().is_some_and(|prev| &line.1 < prev) {
+                return Err(From::from(format!(
+                    "commr: file {} is not in sorted order (line {})",
+                    file_num,
+                    i + 1
+                )));
+            }
+            prev = Some(line.1.clone());
+        }
+        Ok(line)
+    })
 }
 
 fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
-    match filename {
-        "-" => Ok(Box::new(BufReader::new(io::stdin()))),
-        _ => Ok(Box::new(BufReader::new(
+    let reader: Box<dyn BufRead> = match filename {
+        "-" => Box::new(BufReader::new(io::stdin())),
+        _ => Box::new(BufReader::new(
             File::open(filename).map_err(|e| format!("{}: {}", filename, e))?,
-        ))),
+        )),
+    };
+    gunzip(reader).map_err(|e| From::from(format!("{}: {}", filename, e)))
+}
+
+// 先頭が gzip のマジックナンバーなら展開しながら読む
+fn gunzip(mut reader: Box<dyn BufRead>) -> io::Result<Box<dyn BufRead>> {
+    if reader.fill_buf()?.starts_with(&[0x1f, 0x8b]) {
+        Ok(Box::new(BufReader::new(MultiGzDecoder::new(reader))))
+    } else {
+        Ok(reader)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{align, comm, keyed, run_with_writer, Column::*, Config};
+
+    fn config(file1: &str, file2: &str) -> Config {
+        Config {
+            file1: file1.to_string(),
+            file2: file2.to_string(),
+            show_col1: true,
+            show_col2: true,
+            show_col3: true,
+            insensitive: false,
+            ignore_blanks: false,
+            delimiter: "\t".to_string(),
+            total: false,
+            check_order: false,
+            zero_terminated: false,
+            exit_status: false,
+            strip_cr: false,
+            unsorted: false,
+            header: false,
+        }
+    }
+
+    #[test]
+    fn test_run_with_writer() {
+        let mut out = vec![];
+        let res = run_with_writer(
+            config("tests/inputs/file1.txt", "tests/inputs/file2.txt"),
+            &mut out,
+        );
+        assert_eq!(res.unwrap(), 0);
+        assert_eq!(String::from_utf8(out).unwrap(), "\tB\na\nb\n\t\tc\nd\n");
+    }
+
+    #[test]
+    fn test_run_with_writer_duplicates() {
+        // a が 2 行と 1 行、b が 1 行と 2 行
+        let mut out = vec![];
+        let res = run_with_writer(
+            config("tests/inputs/dup1.txt", "tests/inputs/dup2.txt"),
+            &mut out,
+        );
+        assert!(res.is_ok());
+        assert_eq!(String::from_utf8(out).unwrap(), "\t\ta\na\n\t\tb\n\tb\n");
+    }
+
+    #[test]
+    fn test_run_with_writer_suppress() {
+        // 表示しない列の分の空白は詰める
+        let mut out = vec![];
+        let res = run_with_writer(
+            Config {
+                show_col1: false,
+                ..config("tests/inputs/file1.txt", "tests/inputs/file2.txt")
+            },
+            &mut out,
+        );
+        assert!(res.is_ok());
+        assert_eq!(String::from_utf8(out).unwrap(), "B\n\tc\n");
+    }
+
+    #[test]
+    fn test_run_with_writer_header() {
+        let mut out = vec![];
+        let res = run_with_writer(
+            Config {
+                header: true,
+                ..config("tests/inputs/file1.txt", "tests/inputs/file2.txt")
+            },
+            &mut out,
+        );
+        assert!(res.is_ok());
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "tests/inputs/file1.txt\ttests/inputs/file2.txt\tboth\n\tB\na\nb\n\t\tc\nd\n"
+        );
+    }
+
+    #[test]
+    fn test_comm() {
+        let lines = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        let res: Vec<_> = comm(lines(&["a", "b", "c", "d"]), lines(&["B", "c"]), false).collect();
+        assert_eq!(
+            res,
+            [
+                Col2("B".to_string()),
+                Col1("a".to_string()),
+                Col1("b".to_string()),
+                Col3("c".to_string()),
+                Col1("d".to_string()),
+            ]
+        );
+
+        // 大文字小文字を無視する場合、共通の行は file1 の表記で返す
+        let res: Vec<_> = comm(lines(&["a", "b"]), lines(&["B", "c"]), true).collect();
+        assert_eq!(
+            res,
+            [
+                Col1("a".to_string()),
+                Col3("b".to_string()),
+                Col2("c".to_string()),
+            ]
+        );
+
+        assert_eq!(comm(lines(&[]), lines(&[]), false).count(), 0);
+    }
+
+    #[test]
+    fn test_align() {
+        let lines = |v: &[&str]| {
+            v.iter()
+                .map(|s| keyed(s.to_string(), false, false))
+                .collect::<Vec<_>>()
+        };
+
+        // 順序は保ったまま、共通部分列が最長になるように対応づける
+        let res = align(lines(&["c", "a", "x", "b"]), lines(&["c", "y", "a", "b"]));
+        assert_eq!(
+            res,
+            [
+                Col3("c".to_string()),
+                Col2("y".to_string()),
+                Col3("a".to_string()),
+                Col1("x".to_string()),
+                Col3("b".to_string()),
+            ]
+        );
+
+        // 対応づけ方が複数あるときは file1 の行を先に出す
+        let res = align(lines(&["b", "a", "a"]), lines(&["a", "b"]));
+        assert_eq!(
+            res,
+            [
+                Col1("b".to_string()),
+                Col3("a".to_string()),
+                Col1("a".to_string()),
+                Col2("b".to_string()),
+            ]
+        );
+
+        assert!(align(lines(&[]), lines(&[])).is_empty());
+    }
+
+    #[test]
+    fn test_keyed() {
+        assert_eq!(keyed(" \tA".to_string(), false, false).1, " \tA");
+        assert_eq!(keyed(" \tA".to_string(), true, false).1, " \ta");
+        assert_eq!(keyed(" \tA".to_string(), true, true).1, "a");
+        // 表示用の行はそのまま
+        assert_eq!(
+            keyed(" \tA".to_string(), false, true),
+            (" \tA".to_string(), "A".to_string())
+        );
     }
 }
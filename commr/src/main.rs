@@ -1,6 +1,9 @@
 fn main() {
-    if let Err(e) = commr::get_args().and_then(commr::run) {
-        eprintln!("{}", e);
-        std::process::exit(1);
+    match commr::get_args().and_then(commr::run) {
+        Ok(code) => std::process::exit(code),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
     }
 }
@@ -10,6 +10,7 @@ const EMPTY: &str = "tests/inputs/empty.txt";
 const FILE1: &str = "tests/inputs/file1.txt";
 const FILE2: &str = "tests/inputs/file2.txt";
 const BLANK: &str = "tests/inputs/blank.txt";
+const UNSORTED: &str = "tests/inputs/unsorted.txt";
 
 // --------------------------------------------------
 #[test]
@@ -338,3 +339,321 @@ fn file1_file2_123_delim() -> Result<()> {
 fn blank_file1() -> Result<()> {
     run(&[BLANK, FILE1], "tests/expected/blank_file1.out")
 }
+
+// --------------------------------------------------
+#[test]
+fn file1_file2_total() -> Result<()> {
+    run(
+        &["--total", FILE1, FILE2],
+        "tests/expected/file1_file2.total.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn file1_file2_123_total() -> Result<()> {
+    // 表示を抑制した列も件数には含める
+    run(
+        &["-123", "--total", FILE1, FILE2],
+        "tests/expected/file1_file2.123.total.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn file1_file2_check_order() -> Result<()> {
+    run(
+        &["--check-order", FILE1, FILE2],
+        "tests/expected/file1_file2.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_unsorted_check_order() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--check-order", FILE1, UNSORTED])
+        .assert()
+        .failure()
+        .stderr("commr: file 2 is not in sorted order (line 2)\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn unsorted_nocheck_order() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--check-order", "--nocheck-order", UNSORTED, FILE1])
+        .assert()
+        .success();
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn zero1_zero2_z() -> Result<()> {
+    run(
+        &["-z", "tests/inputs/zero1.txt", "tests/inputs/zero2.txt"],
+        "tests/expected/zero1_zero2.z.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn file1_file1_exit_status() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--exit-status", FILE1, FILE1])
+        .assert()
+        .success();
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn file1_file2_exit_status() -> Result<()> {
+    let expected = fs::read_to_string("tests/expected/file1_file2.out")?;
+    Command::cargo_bin(PRG)?
+        .args(["--exit-status", FILE1, FILE2])
+        .assert()
+        .code(1)
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dup1_dup2() -> Result<()> {
+    run(
+        &["tests/inputs/dup1.txt", "tests/inputs/dup2.txt"],
+        "tests/expected/dup1_dup2.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn fruits_i_keeps_case() -> Result<()> {
+    // 大文字小文字を無視して比較しても、表示は元の行のまま
+    run(
+        &[
+            "-i",
+            "tests/inputs/fruits_title.txt",
+            "tests/inputs/fruits_lower.txt",
+        ],
+        "tests/expected/fruits_title_fruits_lower.i.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn file1_file2_gz() -> Result<()> {
+    run(
+        &["tests/inputs/file1.txt.gz", "tests/inputs/file2.txt.gz"],
+        "tests/expected/file1_file2.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn stdin_gz_file2() -> Result<()> {
+    let input = fs::read("tests/inputs/file1.txt.gz")?;
+    let expected = fs::read_to_string("tests/expected/file1_file2.out")?;
+    Command::cargo_bin(PRG)?
+        .args(["-", FILE2])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn file1_file2_pipe_delim() -> Result<()> {
+    run(
+        &[FILE1, FILE2, "--output-delimiter", " | "],
+        "tests/expected/file1_file2.pipe.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn file1_file2_1_pipe_delim() -> Result<()> {
+    run(
+        &[FILE1, FILE2, "-1", "--output-delimiter", " | "],
+        "tests/expected/file1_file2.1.pipe.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn file1_file2_2_pipe_delim() -> Result<()> {
+    run(
+        &[FILE1, FILE2, "-2", "--output-delimiter", " | "],
+        "tests/expected/file1_file2.2.pipe.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn file1_file2_3_pipe_delim() -> Result<()> {
+    run(
+        &[FILE1, FILE2, "-3", "--output-delimiter", " | "],
+        "tests/expected/file1_file2.3.pipe.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn file1_file2_empty_delim() -> Result<()> {
+    // 空の区切り文字を指定すると、列を詰めて出力する
+    run(
+        &[FILE1, FILE2, "--output-delimiter", ""],
+        "tests/expected/file1_file2.nodelim.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn crlf_lf_strip_trailing_cr() -> Result<()> {
+    run(
+        &[
+            "--strip-trailing-cr",
+            "tests/inputs/crlf.txt",
+            "tests/inputs/lf.txt",
+        ],
+        "tests/expected/crlf_lf.strip.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn crlf_lf() -> Result<()> {
+    // 改行区切りでは、行を読むときに CRLF の CR も取り除かれる
+    run(
+        &["tests/inputs/crlf.txt", "tests/inputs/lf.txt"],
+        "tests/expected/crlf_lf.strip.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn crlf_lf_z() -> Result<()> {
+    // -z では CR が残るので、--strip-trailing-cr で取り除く
+    let crlf = "a\r\0b\r\0";
+    Command::cargo_bin(PRG)?
+        .args(["-z", "-", "tests/inputs/lf_z.txt"])
+        .write_stdin(crlf)
+        .assert()
+        .success()
+        .stdout("\ta\0a\r\0\tb\0b\r\0");
+    Command::cargo_bin(PRG)?
+        .args(["-z", "--strip-trailing-cr", "-", "tests/inputs/lf_z.txt"])
+        .write_stdin(crlf)
+        .assert()
+        .success()
+        .stdout("\t\ta\0\t\tb\0");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_dir_file1() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", FILE1])
+        .assert()
+        .failure()
+        .stdout("")
+        .stderr("commr: tests/inputs: Is a directory\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn unsorted1_unsorted2_unsorted() -> Result<()> {
+    // 同じ順序で並んでいるがソートされていない行も共通の列に出る
+    run(
+        &[
+            "--unsorted",
+            "tests/inputs/unsorted1.txt",
+            "tests/inputs/unsorted2.txt",
+        ],
+        "tests/expected/unsorted1_unsorted2.unsorted.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_unsorted_with_check_order() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--unsorted", "--check-order", FILE1, FILE2])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn file1_file2_header() -> Result<()> {
+    let expected = fs::read_to_string("tests/expected/file1_file2.out")?;
+    Command::cargo_bin(PRG)?
+        .args(["--header", FILE1, FILE2])
+        .assert()
+        .success()
+        .stdout(format!("{FILE1}\t{FILE2}\tboth\n{expected}"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn file1_file2_2_pipe_header() -> Result<()> {
+    // 表示しない列の見出しは出さず、区切り文字は -d に合わせる
+    Command::cargo_bin(PRG)?
+        .args(["--header", "-2", "-d", "|", FILE1, FILE2])
+        .assert()
+        .success()
+        .stdout(format!("{FILE1}|both\na\nb\n|c\nd\n"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn indented_ignore_leading_blanks() -> Result<()> {
+    // 先頭の空白を除いて比べ、表示は元の行のまま
+    Command::cargo_bin(PRG)?
+        .args([
+            "--ignore-leading-blanks",
+            "--check-order",
+            "tests/inputs/indented.txt",
+            "tests/inputs/unindented.txt",
+        ])
+        .assert()
+        .success()
+        .stdout("\t\t  a\n b\n\t\t\tc\n\td\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn invalid_utf8() -> Result<()> {
+    // 不正な UTF-8 の行があっても、その後の行を比べ続ける
+    Command::cargo_bin(PRG)?
+        .args(["-", "tests/inputs/a_c.txt"])
+        .write_stdin(&b"a\nb\xff\nc\n"[..])
+        .assert()
+        .success()
+        .stdout("\t\ta\nb\u{fffd}\n\t\tc\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_truncated_gz() -> Result<()> {
+    // 途中で切れた gzip をファイルの終わりとして扱わない
+    Command::cargo_bin(PRG)?
+        .args(["-3", "tests/inputs/cities2.txt", "tests/inputs/trunc.txt.gz"])
+        .assert()
+        .failure()
+        .stderr("tests/inputs/trunc.txt.gz: incomplete deflate stream\n");
+    Ok(())
+}
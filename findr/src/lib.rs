@@ -1,8 +1,15 @@
 use crate::EntryType::*;
-use clap::{App, Arg};
-use regex::Regex;
-use std::error::Error;
-use walkdir::WalkDir;
+use clap::{App, Arg, ArgMatches};
+use regex::{Regex, RegexBuilder};
+use std::{
+    error::Error,
+    fs,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    process::Command,
+    time::{Duration, SystemTime},
+};
+use walkdir::{DirEntry, WalkDir};
 
 type MyResult<T> = Result<T, Box<dyn Error>>;
 
@@ -13,15 +20,217 @@ enum EntryType {
     Link,
 }
 
+// -size や -mtime、-links の N に付ける +/- (なしは等しい)
+#[derive(Debug, Eq, PartialEq)]
+enum Cmp {
+    Greater,
+    Less,
+    Equal,
+}
+
+impl Cmp {
+    fn holds(&self, value: u64, n: u64) -> bool {
+        match self {
+            Cmp::Greater => value > n,
+            Cmp::Less => value < n,
+            Cmp::Equal => value == n,
+        }
+    }
+}
+
+// -size の条件。ファイルサイズを unit バイト単位に切り上げた値と amount を比較する
+#[derive(Debug, Eq, PartialEq)]
+struct SizeFilter {
+    cmp: Cmp,
+    amount: u64,
+    unit: u64,
+}
+
+impl SizeFilter {
+    fn is_match(&self, len: u64) -> bool {
+        self.cmp.holds(len.div_ceil(self.unit), self.amount)
+    }
+}
+
+// -mtime の条件。最終更新からの経過時間を日単位に切り捨てた値と days を比較する
+#[derive(Debug, Eq, PartialEq)]
+struct MtimeFilter {
+    cmp: Cmp,
+    days: u64,
+}
+
+impl MtimeFilter {
+    fn is_match(&self, now: SystemTime, modified: SystemTime) -> bool {
+        // 未来の時刻に更新されたものは経過時間 0 とする
+        let age = now.duration_since(modified).unwrap_or(Duration::ZERO);
+        self.cmp.holds(age.as_secs() / (24 * 60 * 60), self.days)
+    }
+}
+
+// -perm の条件。all が true なら mode のビットをすべて含むもの、false なら等しいもの
+#[derive(Debug, Eq, PartialEq)]
+struct PermFilter {
+    mode: u32,
+    all: bool,
+}
+
+impl PermFilter {
+    fn is_match(&self, mode: u32) -> bool {
+        let mode = mode & 0o7777;
+        if self.all {
+            mode & self.mode == self.mode
+        } else {
+            mode == self.mode
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Config {
     paths: Vec<String>,
+    prune: Vec<Regex>,
+    filters: Vec<FilterGroup>,
+    max_depth: Option<usize>,
+    min_depth: Option<usize>,
+    print0: bool,
+    sort: bool,
+    follow: bool,
+    depth_first: bool,
+    quit: bool,
+    xdev: bool,
+    no_hidden: bool,
+    count: bool,
+    ls: bool,
+    printf: Option<String>,
+    exec: Option<Vec<String>>,
+    exec_batch: bool,
+    delete: bool,
+}
+
+// 1 組のフィルタ。組の中の条件はすべて満たす必要がある
+// --or で区切った組が複数あるときは、どれか 1 つの組を満たせばよい
+#[derive(Debug, Default)]
+struct FilterGroup {
     names: Vec<Regex>,
+    name_all: bool,
+    path_patterns: Vec<Regex>,
     entry_types: Vec<EntryType>,
+    not_names: Vec<Regex>,
+    not_entry_types: Vec<EntryType>,
+    xtypes: Vec<EntryType>,
+    size: Option<SizeFilter>,
+    empty: bool,
+    mtime: Option<MtimeFilter>,
+    newer: Option<SystemTime>,
+    newermt: Option<SystemTime>,
+    samefile: Option<(u64, u64)>,
+    inum: Option<u64>,
+    links: Option<(Cmp, u64)>,
+    readable: bool,
+    writable: bool,
+    executable: bool,
+    perm: Option<PermFilter>,
+    user: Option<u32>,
+    group: Option<u32>,
 }
 
 pub fn get_args() -> MyResult<Config> {
-    let matches = App::new("findr")
+    // --or で区切った引数のまとまりをそれぞれ解析する
+    // 検索パスや出力の指定は最初のまとまりのものを使い、残りからはフィルタだけを読む
+    let args: Vec<String> = std::env::args().collect();
+    let (program, args) = args.split_first().ok_or("findr: missing program name")?;
+    let (args, exec) = split_exec(args)?;
+    let all_matches: Vec<ArgMatches> = split_or_groups(&args)
+        .iter()
+        .map(|group| app().get_matches_from(std::iter::once(program).chain(group)))
+        .collect();
+    for matches in &all_matches[1..] {
+        if matches.occurrences_of("paths") > 0 {
+            return Err(From::from("Search paths must come before --or"));
+        }
+        // 後ろのまとまりのフィルタ以外の指定は使われないので、黙って捨てずにエラーにする
+        if let Some(name) = NON_FILTER_ARGS
+            .iter()
+            .find(|name| matches.occurrences_of(name) > 0)
+        {
+            return Err(From::from(format!(
+                "--{} must come before --or",
+                name.replace('_', "-")
+            )));
+        }
+    }
+    let matches = &all_matches[0];
+
+    // --glob はどのまとまりにあってもすべての組に効く
+    let glob = all_matches.iter().any(|matches| matches.is_present("glob"));
+    let filters = all_matches
+        .iter()
+        .map(|matches| parse_filters(matches, glob))
+        .collect::<MyResult<Vec<_>>>()?;
+
+    let prune = matches
+        .values_of_lossy("prune")
+        .map(|vals| {
+            vals.into_iter()
+                .map(|name| Regex::new(&name).map_err(|_| format!("Invalid --prune \"{}\"", name)))
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .transpose()?
+        .unwrap_or_default();
+
+    let max_depth = matches
+        .value_of("max_depth")
+        .map(|val| parse_depth(val).map_err(|_| format!("Invalid --max-depth \"{}\"", val)))
+        .transpose()?;
+
+    let min_depth = matches
+        .value_of("min_depth")
+        .map(|val| parse_depth(val).map_err(|_| format!("Invalid --min-depth \"{}\"", val)))
+        .transpose()?;
+
+    // --exec cmd {} + のときは見つかったパスをまとめて実行する
+    let (exec, exec_batch) = match exec {
+        None => (None, false),
+        Some((command, batch)) => {
+            if command.is_empty() || (batch && command.len() == 1) {
+                return Err(From::from("--exec: missing command"));
+            }
+            if batch
+                && command[..command.len() - 1]
+                    .iter()
+                    .any(|arg| arg.contains("{}"))
+            {
+                return Err(From::from("--exec: only one {} is supported with {} +"));
+            }
+            (Some(command), batch)
+        }
+    };
+
+    Ok(Config {
+        paths: matches.values_of_lossy("paths").unwrap(),
+        prune,
+        filters,
+        max_depth,
+        min_depth,
+        print0: matches.is_present("print0"),
+        sort: matches.is_present("sort"),
+        follow: matches.is_present("follow"),
+        // ディレクトリは中身を消してから消す
+        depth_first: matches.is_present("depth") || matches.is_present("delete"),
+        quit: matches.is_present("quit"),
+        xdev: matches.is_present("xdev"),
+        no_hidden: matches.is_present("no_hidden"),
+        count: matches.is_present("count"),
+        ls: matches.is_present("ls"),
+        printf: matches.value_of("printf").map(String::from),
+        exec,
+        exec_batch,
+        delete: matches.is_present("delete"),
+    })
+}
+
+fn app() -> App<'static, 'static> {
+    App::new("findr")
         .version("0.1.0")
         .author("Fukkatsuso <fukkatsuso.git+github@gmail.com>")
         .about("Rust find")
@@ -40,6 +249,32 @@ pub fn get_args() -> MyResult<Config> {
                 .help("Name")
                 .multiple(true),
         )
+        .arg(
+            Arg::with_name("inames")
+                .long("iname")
+                .value_name("NAME")
+                .help("Name (case insensitive)")
+                .multiple(true),
+        )
+        .arg(
+            Arg::with_name("name_all")
+                .long("name-all")
+                .help("Require every --name and --iname to match (default: any of them)")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("glob")
+                .short("g")
+                .long("glob")
+                .help("Treat --name, --iname and --not-name as shell globs"),
+        )
+        .arg(
+            Arg::with_name("path_patterns")
+                .long("path")
+                .value_name("PATTERN")
+                .help("Pattern matched against the whole path")
+                .multiple(true),
+        )
         .arg(
             Arg::with_name("type")
                 .short("t")
@@ -49,63 +284,773 @@ pub fn get_args() -> MyResult<Config> {
                 .possible_values(&["f", "d", "l"])
                 .multiple(true),
         )
-        .get_matches();
+        .arg(
+            Arg::with_name("not_names")
+                .long("not-name")
+                .value_name("NAME")
+                .help("Exclude entries matching NAME")
+                .multiple(true),
+        )
+        .arg(
+            Arg::with_name("prune")
+                .long("prune")
+                .value_name("PATTERN")
+                .help("Do not descend into directories whose name matches PATTERN")
+                .multiple(true),
+        )
+        .arg(
+            Arg::with_name("not_type")
+                .long("not-type")
+                .value_name("TYPE")
+                .help("Exclude entries of TYPE")
+                .possible_values(&["f", "d", "l"])
+                .multiple(true),
+        )
+        .arg(
+            Arg::with_name("xtype")
+                .long("xtype")
+                .value_name("TYPE")
+                .help("Entry type, following symbolic links")
+                .possible_values(&["f", "d", "l"])
+                .multiple(true),
+        )
+        .arg(
+            Arg::with_name("max_depth")
+                .long("max-depth")
+                .value_name("N")
+                .help("Descend at most N levels below the search paths"),
+        )
+        .arg(
+            Arg::with_name("min_depth")
+                .long("min-depth")
+                .value_name("N")
+                .help("Ignore entries less than N levels below the search paths"),
+        )
+        .arg(
+            Arg::with_name("size")
+                .long("size")
+                .value_name("[+-]N[bcwkMG]")
+                .help("File size")
+                .allow_hyphen_values(true),
+        )
+        .arg(
+            Arg::with_name("mtime")
+                .long("mtime")
+                .value_name("[+-]N")
+                .help("Modified N days ago (+N: more than, -N: less than)")
+                .allow_hyphen_values(true),
+        )
+        .arg(
+            Arg::with_name("newer")
+                .long("newer")
+                .value_name("FILE")
+                .help("Modified more recently than FILE"),
+        )
+        .arg(
+            Arg::with_name("newermt")
+                .long("newermt")
+                .value_name("DATE")
+                .help("Modified after DATE (YYYY-MM-DD [HH:MM[:SS]], local time)"),
+        )
+        .arg(
+            Arg::with_name("samefile")
+                .long("samefile")
+                .value_name("FILE")
+                .help("Same file as FILE (hard links included)"),
+        )
+        .arg(
+            Arg::with_name("inum")
+                .long("inum")
+                .value_name("N")
+                .help("Inode number N"),
+        )
+        .arg(
+            Arg::with_name("links")
+                .long("links")
+                .value_name("[+-]N")
+                .help("Hard link count N (+N: more than, -N: less than)")
+                .allow_hyphen_values(true),
+        )
+        .arg(
+            Arg::with_name("readable")
+                .long("readable")
+                .help("Readable by the current user")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("writable")
+                .long("writable")
+                .help("Writable by the current user")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("executable")
+                .long("executable")
+                .help("Executable (or searchable, for directories) by the current user")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("perm")
+                .long("perm")
+                .value_name("[-]MODE")
+                .help("Permission bits equal to octal MODE (-MODE: include all of them)")
+                .allow_hyphen_values(true),
+        )
+        .arg(
+            Arg::with_name("user")
+                .long("user")
+                .value_name("NAME")
+                .help("Owned by user NAME (or numeric user ID)"),
+        )
+        .arg(
+            Arg::with_name("group")
+                .long("group")
+                .value_name("NAME")
+                .help("Owned by group NAME (or numeric group ID)"),
+        )
+        .arg(
+            Arg::with_name("empty")
+                .long("empty")
+                .help("Empty files and directories")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("print0")
+                .short("0")
+                .long("print0")
+                .help("Separate paths with NUL instead of newline")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("sort")
+                .long("sort")
+                .help("Sort the results by path before printing")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("follow")
+                .short("L")
+                .long("follow")
+                .help("Follow symbolic links")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("depth")
+                .long("depth")
+                .help("Process directory contents before the directory itself")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("quit")
+                .long("quit")
+                .help("Stop after the first match")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("xdev")
+                .long("xdev")
+                .help("Do not descend into directories on other filesystems")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("hidden")
+                .long("hidden")
+                .help("Search hidden files and directories (default)")
+                .takes_value(false)
+                .overrides_with("no_hidden"),
+        )
+        .arg(
+            Arg::with_name("no_hidden")
+                .long("no-hidden")
+                .help("Skip hidden files and directories")
+                .takes_value(false)
+                .overrides_with("hidden"),
+        )
+        .arg(
+            Arg::with_name("count")
+                .long("count")
+                .help("Print only the number of matches")
+                .takes_value(false)
+                .conflicts_with("exec"),
+        )
+        .arg(
+            Arg::with_name("ls")
+                .long("ls")
+                .help("Print matches in ls -l style long format")
+                .takes_value(false)
+                .conflicts_with("exec"),
+        )
+        .arg(
+            Arg::with_name("printf")
+                .long("printf")
+                .value_name("FORMAT")
+                .help("Print matches using FORMAT (%p %f %s %m %y, \\n \\t)")
+                .conflicts_with_all(&["exec", "ls"]),
+        )
+        .arg(
+            Arg::with_name("exec")
+                .long("exec")
+                .value_name("COMMAND")
+                .help("Run COMMAND for each path ({} is replaced by the path, end with ; or {} +)")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("delete")
+                .long("delete")
+                .help("Delete matches (implies --depth)")
+                .takes_value(false)
+                .conflicts_with_all(&["exec", "count", "ls", "printf", "sort"]),
+        )
+        .arg(
+            Arg::with_name("or").long("or").help(
+                "Match entries passing either the filters before or after this (filters only)",
+            ),
+        )
+}
+
+// 値を取るオプション。直後の --or は区切りではなく値として扱う
+const VALUE_OPTIONS: &[&str] = &[
+    "-n",
+    "--name",
+    "--iname",
+    "--path",
+    "-t",
+    "--type",
+    "--not-name",
+    "--prune",
+    "--not-type",
+    "--xtype",
+    "--max-depth",
+    "--min-depth",
+    "--size",
+    "--mtime",
+    "--newer",
+    "--newermt",
+    "--samefile",
+    "--inum",
+    "--links",
+    "--perm",
+    "--user",
+    "--group",
+    "--printf",
+];
 
-    let names = matches
+// 最初のまとまりにしか書けない、フィルタ以外の引数
+const NON_FILTER_ARGS: &[&str] = &[
+    "prune",
+    "max_depth",
+    "min_depth",
+    "print0",
+    "sort",
+    "follow",
+    "depth",
+    "quit",
+    "xdev",
+    "hidden",
+    "no_hidden",
+    "count",
+    "ls",
+    "printf",
+    "exec",
+    "delete",
+];
+
+// --exec のコマンドと、{} + で終わったか
+type ExecCommand = (Vec<String>, bool);
+
+// --exec のコマンドを ; または {} + までとして取り出す
+// clap には値のない --exec だけを渡し、ほかのオプションとの衝突を調べさせる
+// {} + のときは true を返し、コマンドの最後に {} を残す
+fn split_exec(args: &[String]) -> MyResult<(Vec<String>, Option<ExecCommand>)> {
+    let mut rest = vec![];
+    let mut exec = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let prev_takes_value = rest
+            .last()
+            .is_some_and(|prev: &String| VALUE_OPTIONS.contains(&prev.as_str()));
+        rest.push(arg.clone());
+        if arg != "--exec" || prev_takes_value {
+            continue;
+        }
+        let mut command: Vec<String> = vec![];
+        let batch = loop {
+            match args.next().map(String::as_str) {
+                None => return Err(From::from("--exec: missing terminating ; or {} +")),
+                Some(";") => break false,
+                Some("+") if command.last().is_some_and(|arg| arg == "{}") => break true,
+                Some(arg) => command.push(arg.to_string()),
+            }
+        };
+        exec = Some((command, batch));
+    }
+    Ok((rest, exec))
+}
+
+// 引数を --or で区切る
+// 値を取るオプションの直後の --or は区切りにしない
+fn split_or_groups(args: &[String]) -> Vec<Vec<String>> {
+    let mut groups: Vec<Vec<String>> = vec![vec![]];
+    for arg in args {
+        let group = groups.last_mut().unwrap();
+        if arg == "--or" {
+            match group.last_mut() {
+                // -n --or は -n=--or として clap に値を渡す
+                Some(prev) if VALUE_OPTIONS.contains(&prev.as_str()) => {
+                    prev.push('=');
+                    prev.push_str(arg);
+                }
+                _ => groups.push(vec![]),
+            }
+            continue;
+        }
+        group.push(arg.clone());
+    }
+    groups
+}
+
+// --glob のときは名前のパターンをグロブとして正規表現に変換する
+fn parse_filters(matches: &ArgMatches, glob: bool) -> MyResult<FilterGroup> {
+    let name_pattern = |name: &str| {
+        if glob {
+            glob_to_pattern(name)
+        } else {
+            name.to_string()
+        }
+    };
+
+    let mut names = matches
         .values_of_lossy("names")
         .map(|vals| {
             vals.into_iter()
-                .map(|name| Regex::new(&name).map_err(|_| format!("Invalid --name \"{}\"", name)))
+                .map(|name| {
+                    Regex::new(&name_pattern(&name))
+                        .map_err(|_| format!("Invalid --name \"{}\"", name))
+                })
                 .collect::<Result<Vec<_>, _>>()
         })
         .transpose()?
         .unwrap_or_default();
 
-    let entry_types = matches
-        .values_of_lossy("type")
+    // --iname は --name と同じく、どれか 1 つにマッチすればよい
+    let inames = matches
+        .values_of_lossy("inames")
         .map(|vals| {
-            vals.iter()
-                .map(|t| match t.as_str() {
-                    "f" => File,
-                    "d" => Dir,
-                    "l" => Link,
-                    _ => unreachable!("Invalid type"),
+            vals.into_iter()
+                .map(|name| {
+                    RegexBuilder::new(&name_pattern(&name))
+                        .case_insensitive(true)
+                        .build()
+                        .map_err(|_| format!("Invalid --iname \"{}\"", name))
                 })
-                .collect()
+                .collect::<Result<Vec<_>, _>>()
         })
+        .transpose()?
         .unwrap_or_default();
+    names.extend(inames);
 
-    Ok(Config {
-        paths: matches.values_of_lossy("paths").unwrap(),
-        names: names,
-        entry_types: entry_types,
+    let path_patterns = matches
+        .values_of_lossy("path_patterns")
+        .map(|vals| {
+            vals.into_iter()
+                .map(|path| Regex::new(&path).map_err(|_| format!("Invalid --path \"{}\"", path)))
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .transpose()?
+        .unwrap_or_default();
+
+    let not_names = matches
+        .values_of_lossy("not_names")
+        .map(|vals| {
+            vals.into_iter()
+                .map(|name| {
+                    Regex::new(&name_pattern(&name))
+                        .map_err(|_| format!("Invalid --not-name \"{}\"", name))
+                })
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .transpose()?
+        .unwrap_or_default();
+
+    let entry_types_of = |arg: &str| -> MyResult<Vec<EntryType>> {
+        Ok(matches
+            .values_of_lossy(arg)
+            .map(|vals| vals.iter().map(|t| parse_entry_type(t)).collect())
+            .transpose()?
+            .unwrap_or_default())
+    };
+    let entry_types = entry_types_of("type")?;
+    let not_entry_types = entry_types_of("not_type")?;
+    let xtypes = entry_types_of("xtype")?;
+
+    let size = matches
+        .value_of("size")
+        .map(|val| parse_size(val).map_err(|_| format!("Invalid --size \"{}\"", val)))
+        .transpose()?;
+
+    let mtime = matches
+        .value_of("mtime")
+        .map(|val| parse_mtime(val).map_err(|_| format!("Invalid --mtime \"{}\"", val)))
+        .transpose()?;
+
+    let perm = matches
+        .value_of("perm")
+        .map(|val| parse_perm(val).map_err(|_| format!("Invalid --perm \"{}\"", val)))
+        .transpose()?;
+
+    let user = matches
+        .value_of("user")
+        .map(|val| parse_user(val).map_err(|_| format!("Invalid --user \"{}\"", val)))
+        .transpose()?;
+
+    let group = matches
+        .value_of("group")
+        .map(|val| parse_group(val).map_err(|_| format!("Invalid --group \"{}\"", val)))
+        .transpose()?;
+
+    let newer = matches
+        .value_of("newer")
+        .map(|file| {
+            fs::metadata(file)
+                .and_then(|metadata| metadata.modified())
+                .map_err(|e| format!("{}: {}", file, e))
+        })
+        .transpose()?;
+
+    let newermt = matches
+        .value_of("newermt")
+        .map(|val| parse_date(val).map_err(|_| format!("Invalid --newermt \"{}\"", val)))
+        .transpose()?;
+
+    // 比較するデバイスと inode は最初に 1 回だけ調べる
+    let samefile = matches
+        .value_of("samefile")
+        .map(|file| {
+            fs::metadata(file)
+                .map_err(|e| format!("{}: {}", file, e))
+                .and_then(|metadata| {
+                    file_id(&metadata)
+                        .ok_or_else(|| format!("{}: --samefile is not supported", file))
+                })
+        })
+        .transpose()?;
+
+    let inum = matches
+        .value_of("inum")
+        .map(|val| parse_digits(val).map_err(|_| format!("Invalid --inum \"{}\"", val)))
+        .transpose()?;
+
+    let links = matches
+        .value_of("links")
+        .map(|val| parse_links(val).map_err(|_| format!("Invalid --links \"{}\"", val)))
+        .transpose()?;
+
+    Ok(FilterGroup {
+        names,
+        name_all: matches.is_present("name_all"),
+        path_patterns,
+        entry_types,
+        not_names,
+        not_entry_types,
+        xtypes,
+        size,
+        empty: matches.is_present("empty"),
+        mtime,
+        perm,
+        user,
+        group,
+        newer,
+        newermt,
+        samefile,
+        inum,
+        links,
+        readable: matches.is_present("readable"),
+        writable: matches.is_present("writable"),
+        executable: matches.is_present("executable"),
+    })
+}
+
+// グロブをファイル名全体にマッチする正規表現に変換する
+// * は任意の文字列、? は任意の 1 文字で、それ以外の文字はそのままの意味になる
+fn glob_to_pattern(pat: &str) -> String {
+    let mut pattern = String::from("^");
+    for c in pat.chars() {
+        match c {
+            '*' => pattern.push_str(".*"),
+            '?' => pattern.push('.'),
+            _ => pattern.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    pattern.push('$');
+    pattern
+}
+
+// 特殊文字はすべてエスケープされるので、変換結果は常に正しい正規表現になる
+pub fn glob_to_regex(pat: &str) -> Regex {
+    Regex::new(&glob_to_pattern(pat)).unwrap()
+}
+
+// clap の possible_values で弾かれるはずだが、許可する値が変わっても panic しないようにする
+fn parse_entry_type(t: &str) -> MyResult<EntryType> {
+    match t {
+        "f" => Ok(File),
+        "d" => Ok(Dir),
+        "l" => Ok(Link),
+        _ => Err(From::from(format!("Invalid type \"{}\"", t))),
+    }
+}
+
+fn parse_depth(val: &str) -> MyResult<usize> {
+    val.parse().map_err(|_| From::from(val))
+}
+
+// find -size と同じく、単位を省略した場合は 512 バイトのブロック単位
+fn parse_size(val: &str) -> MyResult<SizeFilter> {
+    let (cmp, rest) = parse_cmp(val);
+    let (amount, unit) = match rest.char_indices().last() {
+        Some((i, c)) if c.is_ascii_alphabetic() => {
+            let unit = match c {
+                'b' => 512,
+                'c' => 1,
+                'w' => 2,
+                'k' => 1024,
+                'M' => 1024 * 1024,
+                'G' => 1024 * 1024 * 1024,
+                _ => return Err(From::from(val)),
+            };
+            (&rest[..i], unit)
+        }
+        _ => (rest, 512),
+    };
+    Ok(SizeFilter {
+        cmp,
+        amount: parse_digits(amount).map_err(|_| val)?,
+        unit,
+    })
+}
+
+fn parse_mtime(val: &str) -> MyResult<MtimeFilter> {
+    let (cmp, days) = parse_cmp(val);
+    Ok(MtimeFilter {
+        cmp,
+        days: parse_digits(days).map_err(|_| val)?,
     })
 }
 
-pub fn run(config: Config) -> MyResult<()> {
-    for path in config.paths {
-        for entry in WalkDir::new(path) {
+// 日付だけなら、その日の 0 時 (ローカル時刻) とする
+fn parse_date(val: &str) -> MyResult<SystemTime> {
+    use chrono::{Local, NaiveDate, NaiveDateTime, TimeZone};
+
+    let datetime = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(val, format).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(val, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })
+        .ok_or(val)?;
+    // 夏時間の切り替えで 2 回ある時刻は早い方、存在しない時刻はエラーにする
+    let datetime = Local.from_local_datetime(&datetime).earliest().ok_or(val)?;
+    Ok(datetime.into())
+}
+
+fn parse_links(val: &str) -> MyResult<(Cmp, u64)> {
+    let (cmp, count) = parse_cmp(val);
+    Ok((cmp, parse_digits(count).map_err(|_| val)?))
+}
+
+fn parse_perm(val: &str) -> MyResult<PermFilter> {
+    let (all, mode) = match val.strip_prefix('-') {
+        Some(mode) => (true, mode),
+        None => (false, val),
+    };
+    if mode.is_empty() || !mode.bytes().all(|b| (b'0'..=b'7').contains(&b)) {
+        return Err(From::from(val));
+    }
+    match u32::from_str_radix(mode, 8) {
+        Ok(mode) if mode <= 0o7777 => Ok(PermFilter { mode, all }),
+        _ => Err(From::from(val)),
+    }
+}
+
+// 数字ならそのまま ID とし、それ以外はユーザー名として uid を調べる
+fn parse_user(val: &str) -> MyResult<u32> {
+    if let Ok(uid) = parse_digits(val) {
+        return Ok(u32::try_from(uid)?);
+    }
+    #[cfg(unix)]
+    if let Some(user) = users::get_user_by_name(val) {
+        return Ok(user.uid());
+    }
+    Err(From::from(val))
+}
+
+// 数字ならそのまま ID とし、それ以外はグループ名として gid を調べる
+fn parse_group(val: &str) -> MyResult<u32> {
+    if let Ok(gid) = parse_digits(val) {
+        return Ok(u32::try_from(gid)?);
+    }
+    #[cfg(unix)]
+    if let Some(group) = users::get_group_by_name(val) {
+        return Ok(group.gid());
+    }
+    Err(From::from(val))
+}
+
+// 先頭の +/- を比較の種類として取り出す
+fn parse_cmp(val: &str) -> (Cmp, &str) {
+    match val.as_bytes().first() {
+        Some(b'+') => (Cmp::Greater, &val[1..]),
+        Some(b'-') => (Cmp::Less, &val[1..]),
+        _ => (Cmp::Equal, val),
+    }
+}
+
+// u64::from_str は先頭の + を受け付けるので、数字だけかを先に確かめる
+fn parse_digits(val: &str) -> MyResult<u64> {
+    if val.is_empty() || !val.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(From::from(val));
+    }
+    Ok(val.parse()?)
+}
+
+/// 終了ステータスを返す。
+/// --exec で実行したコマンドが 1 つでも失敗すれば 1、それ以外は 0 を返す。
+pub fn run(config: Config) -> MyResult<i32> {
+    let mut out = BufWriter::new(io::stdout().lock());
+    let code = run_with_writer(config, &mut out)?;
+    out.flush()?;
+    Ok(code)
+}
+
+/// run と同じ検索を行い、見つかったパスを out に書き込む
+pub fn run_with_writer(config: Config, out: &mut impl Write) -> MyResult<i32> {
+    let terminator = if config.print0 { b'\0' } else { b'\n' };
+    if config.count {
+        let mut count = 0;
+        walk(&config, &mut |_| {
+            count += 1;
+            Ok(())
+        })?;
+        writeln!(out, "{}", count)?;
+        return Ok(0);
+    }
+
+    let mut ok = true;
+    let mut batch = ExecBatch::default();
+    let mut handle = |entry: DirEntry| -> MyResult<()> {
+        match &config.exec {
+            Some(command) if config.exec_batch => {
+                if let Some(paths) = batch.push(command, entry.into_path()) {
+                    ok &= exec_paths(out, command, &paths)?;
+                }
+            }
+            _ => ok &= emit(&config, out, &entry, terminator)?,
+        }
+        Ok(())
+    };
+    // --sort のときだけすべて集めてから出力し、それ以外は見つけたそばから出力する
+    if config.sort {
+        for entry in collect_entries(&config)? {
+            handle(entry)?;
+        }
+    } else {
+        walk(&config, &mut handle)?;
+    }
+    if let Some(command) = config.exec.as_ref().filter(|_| config.exec_batch) {
+        let paths = batch.take();
+        if !paths.is_empty() {
+            ok &= exec_paths(out, command, &paths)?;
+        }
+    }
+    Ok(if ok { 0 } else { 1 })
+}
+
+// 条件に一致するパスを出力せずに返す (--sort が指定されていれば並べ替える)
+// --quit が指定されていれば最初に一致したものを見つけた時点で走査をやめる
+// 走査中のエラーは表示して続ける
+pub fn find_entries(config: &Config) -> MyResult<Vec<PathBuf>> {
+    Ok(collect_entries(config)?
+        .into_iter()
+        .map(DirEntry::into_path)
+        .collect())
+}
+
+// find_entries と同じだが、出力でメタデータを使えるよう DirEntry のまま返す
+fn collect_entries(config: &Config) -> MyResult<Vec<DirEntry>> {
+    let mut found = vec![];
+    walk(config, &mut |entry| {
+        found.push(entry);
+        Ok(())
+    })?;
+    if config.sort {
+        found.sort_by(|a, b| a.path().cmp(b.path()));
+    }
+    Ok(found)
+}
+
+// 条件に一致するものを見つけた順に found に渡す
+// --quit が指定されていれば最初に一致したものを渡した時点で走査をやめる
+// 走査中のエラーは表示して続ける
+fn walk(config: &Config, found: &mut impl FnMut(DirEntry) -> MyResult<()>) -> MyResult<()> {
+    let now = SystemTime::now();
+    for path in &config.paths {
+        // 深さ 0 は検索パスそのもの
+        // リンクをたどる場合のループは WalkDir が検出してエラーとして返すので、
+        // ほかのエラーと同じく表示して続ける
+        let mut walker = WalkDir::new(path)
+            .follow_links(config.follow)
+            .contents_first(config.depth_first);
+        if let Some(depth) = config.max_depth {
+            walker = walker.max_depth(depth);
+        }
+        if let Some(depth) = config.min_depth {
+            walker = walker.min_depth(depth);
+        }
+        // --xdev のときは検索パスと同じファイルシステムにあるかを比べる
+        let root_dev = if config.xdev {
+            fs::metadata(path)
+                .ok()
+                .and_then(|metadata| file_device(&metadata))
+        } else {
+            None
+        };
+        // --prune に一致するディレクトリや別のファイルシステムにあるディレクトリは
+        // その中身も含めて飛ばす
+        // --no-hidden のときは . で始まるファイルとディレクトリを飛ばすが、
+        // 検索パスそのもの (深さ 0) は . で始まっていても飛ばさない
+        let walker = walker.into_iter().filter_entry(|entry| {
+            if config.no_hidden && entry.depth() > 0 && is_hidden(entry) {
+                return false;
+            }
+            if !entry.file_type().is_dir() {
+                return true;
+            }
+            if has_name(&config.prune, entry) {
+                return false;
+            }
+            // --xdev でなければメタデータは取得しない
+            root_dev.is_none()
+                || !is_other_device(
+                    root_dev,
+                    entry.metadata().ok().and_then(|m| file_device(&m)),
+                )
+        });
+        for entry in walker {
             match entry {
                 Err(e) => eprintln!("{}", e),
                 Ok(entry) => {
-                    // filtering by type
-                    let type_ok = config.entry_types.is_empty()
-                        || (config.entry_types.contains(&Dir) && entry.file_type().is_dir())
-                        || (config.entry_types.contains(&File) && entry.file_type().is_file())
-                        || (config.entry_types.contains(&Link) && entry.file_type().is_symlink());
-
-                    // filtering by name
-                    let name_ok = config.names.is_empty()
-                        || config
-                            .names
-                            .iter()
-                            .any(|regex| match entry.path().file_name() {
-                                Some(name) => regex.is_match(name.to_str().unwrap()),
-                                None => false,
-                            });
-
-                    if type_ok && name_ok {
-                        println!("{}", entry.path().display());
+                    if config
+                        .filters
+                        .iter()
+                        .any(|filters| filters.is_match(&entry, now))
+                    {
+                        found(entry)?;
+                        // --quit のときは最初に見つかったものだけを渡す
+                        if config.quit {
+                            return Ok(());
+                        }
                     }
                 }
             }
@@ -113,3 +1058,1194 @@ pub fn run(config: Config) -> MyResult<()> {
     }
     Ok(())
 }
+
+impl FilterGroup {
+    // entry が組の条件をすべて満たすか
+    fn is_match(&self, entry: &DirEntry, now: SystemTime) -> bool {
+        // filtering by type (--not-type に該当するものは除く)
+        let type_ok = (self.entry_types.is_empty() || has_type(&self.entry_types, entry))
+            && !has_type(&self.not_entry_types, entry)
+            && (self.xtypes.is_empty() || has_xtype(&self.xtypes, entry));
+
+        // filtering by name (--not-name に該当するものは除く)
+        // 既定では --name のどれかにマッチすればよく、--name-all のときはすべてにマッチする必要がある
+        let name_ok = (self.names.is_empty()
+            || if self.name_all {
+                has_all_names(&self.names, entry)
+            } else {
+                has_name(&self.names, entry)
+            })
+            && !has_name(&self.not_names, entry);
+
+        // filtering by path (UTF-8 として不正なパスは置換文字に変えてマッチさせる)
+        let path_ok = self.path_patterns.is_empty()
+            || self
+                .path_patterns
+                .iter()
+                .any(|regex| regex.is_match(&entry.path().to_string_lossy()));
+
+        // filtering by size (通常のファイルのみ)
+        let size_ok = match &self.size {
+            None => true,
+            Some(filter) => {
+                entry.file_type().is_file()
+                    && entry.metadata().is_ok_and(|m| filter.is_match(m.len()))
+            }
+        };
+
+        // filtering by modification time
+        let time_ok = (self.mtime.is_none() && self.newer.is_none() && self.newermt.is_none())
+            || entry
+                .metadata()
+                .ok()
+                .and_then(|metadata| metadata.modified().ok())
+                .is_some_and(|modified| {
+                    self.mtime
+                        .as_ref()
+                        .is_none_or(|filter| filter.is_match(now, modified))
+                        && self.newer.is_none_or(|newer| modified > newer)
+                        && self.newermt.is_none_or(|newermt| modified > newermt)
+                });
+
+        // filtering by emptiness
+        let empty_ok = !self.empty || is_empty(entry);
+
+        // filtering by permission (通常のファイルとディレクトリのみ)
+        let perm_ok = match &self.perm {
+            None => true,
+            Some(filter) => {
+                (entry.file_type().is_file() || entry.file_type().is_dir())
+                    && entry
+                        .metadata()
+                        .ok()
+                        .and_then(|metadata| file_mode(&metadata))
+                        .is_some_and(|mode| filter.is_match(mode))
+            }
+        };
+
+        // filtering by owner
+        let owner_ok = (self.user.is_none() && self.group.is_none())
+            || entry
+                .metadata()
+                .ok()
+                .and_then(|metadata| file_owner(&metadata))
+                .is_some_and(|(uid, gid)| {
+                    self.user.is_none_or(|user| uid == user)
+                        && self.group.is_none_or(|group| gid == group)
+                });
+
+        // filtering by identity (デバイスと inode が同じもの)
+        let samefile_ok = match self.samefile {
+            None => true,
+            Some(id) => {
+                entry
+                    .metadata()
+                    .ok()
+                    .and_then(|metadata| file_id(&metadata))
+                    == Some(id)
+            }
+        };
+
+        // filtering by inode number
+        let inum_ok = match self.inum {
+            None => true,
+            Some(inum) => {
+                entry
+                    .metadata()
+                    .ok()
+                    .and_then(|metadata| file_id(&metadata))
+                    .map(|(_, ino)| ino)
+                    == Some(inum)
+            }
+        };
+
+        // filtering by hard link count
+        let links_ok = match &self.links {
+            None => true,
+            Some((cmp, count)) => entry
+                .metadata()
+                .ok()
+                .and_then(|metadata| file_nlink(&metadata))
+                .is_some_and(|nlink| cmp.holds(nlink, *count)),
+        };
+
+        // filtering by access for the current user
+        let access_ok = (!self.readable || is_accessible(entry.path(), Access::Read))
+            && (!self.writable || is_accessible(entry.path(), Access::Write))
+            && (!self.executable || is_accessible(entry.path(), Access::Execute));
+
+        type_ok
+            && name_ok
+            && path_ok
+            && size_ok
+            && time_ok
+            && empty_ok
+            && perm_ok
+            && owner_ok
+            && samefile_ok
+            && inum_ok
+            && links_ok
+            && access_ok
+    }
+}
+
+// 見つかったパスを出力するか、--exec のコマンドを実行する
+// コマンドが失敗したときは false を返す
+fn emit(config: &Config, out: &mut impl Write, entry: &DirEntry, terminator: u8) -> MyResult<bool> {
+    match &config.exec {
+        Some(command) => exec_command(out, command, &[entry.path()]),
+        None if config.delete => Ok(delete_entry(entry)),
+        None if config.printf.is_some() => {
+            let fmt = config.printf.as_deref().unwrap_or_default();
+            out.write_all(apply_printf(fmt, entry)?.as_bytes())?;
+            Ok(true)
+        }
+        None if config.ls => {
+            out.write_all(format_long(entry)?.as_bytes())?;
+            out.write_all(&[terminator])?;
+            Ok(true)
+        }
+        None => {
+            write_path(out, entry.path(), terminator)?;
+            Ok(true)
+        }
+    }
+}
+
+// find -ls と同じく inode、ブロック数 (1K 単位)、パーミッション、リンク数、
+// 所有者、グループ、サイズ、更新日時、パスを並べる
+#[cfg(unix)]
+fn format_long(entry: &DirEntry) -> MyResult<String> {
+    use chrono::{DateTime, Local};
+    use std::os::unix::fs::MetadataExt;
+
+    let metadata = entry.metadata()?;
+    let user = users::get_user_by_uid(metadata.uid())
+        .map(|u| u.name().to_string_lossy().into_owned())
+        .unwrap_or_else(|| metadata.uid().to_string());
+    let group = users::get_group_by_gid(metadata.gid())
+        .map(|g| g.name().to_string_lossy().into_owned())
+        .unwrap_or_else(|| metadata.gid().to_string());
+    let modified: DateTime<Local> = DateTime::from(metadata.modified()?);
+
+    let mut line = format!(
+        "{:>9} {:>6} {}{} {:>3} {:<8} {:<8} {:>8} {} {}",
+        metadata.ino(),
+        metadata.blocks().div_ceil(2),
+        file_type_char(&metadata.file_type()),
+        format_mode(metadata.mode()),
+        metadata.nlink(),
+        user,
+        group,
+        metadata.len(),
+        modified.format("%b %d %y %H:%M"),
+        entry.path().display()
+    );
+    // シンボリックリンクはリンク先も表示する
+    if metadata.file_type().is_symlink() {
+        if let Ok(target) = fs::read_link(entry.path()) {
+            line.push_str(&format!(" -> {}", target.display()));
+        }
+    }
+    Ok(line)
+}
+
+#[cfg(not(unix))]
+fn format_long(_entry: &DirEntry) -> MyResult<String> {
+    Err(From::from("--ls is not supported on this platform"))
+}
+
+// ls -l の先頭の 1 文字
+fn file_type_char(file_type: &fs::FileType) -> char {
+    if file_type.is_symlink() {
+        return 'l';
+    }
+    if file_type.is_dir() {
+        return 'd';
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt;
+
+        if file_type.is_char_device() {
+            return 'c';
+        }
+        if file_type.is_block_device() {
+            return 'b';
+        }
+        if file_type.is_fifo() {
+            return 'p';
+        }
+        if file_type.is_socket() {
+            return 's';
+        }
+    }
+    '-'
+}
+
+// --printf の書式に従って entry を文字列にする
+// 知らない指示子やエスケープはそのまま出力する
+fn apply_printf(fmt: &str, entry: &DirEntry) -> MyResult<String> {
+    let mut result = String::new();
+    let mut chars = fmt.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '%' => match chars.next() {
+                Some('p') => result.push_str(&entry.path().to_string_lossy()),
+                Some('f') => {
+                    // 検索パスが / などのときはファイル名がないのでパスをそのまま使う
+                    let name = entry.path().file_name().unwrap_or(entry.path().as_os_str());
+                    result.push_str(&name.to_string_lossy());
+                }
+                Some('s') => result.push_str(&entry.metadata()?.len().to_string()),
+                Some('m') => {
+                    let mode = file_mode(&entry.metadata()?)
+                        .ok_or("%m is not supported on this platform")?;
+                    result.push_str(&format!("{:o}", mode & 0o7777));
+                }
+                Some('y') => result.push(match file_type_char(&entry.file_type()) {
+                    '-' => 'f',
+                    c => c,
+                }),
+                Some('%') => result.push('%'),
+                Some(other) => {
+                    result.push('%');
+                    result.push(other);
+                }
+                None => result.push('%'),
+            },
+            '\\' => match chars.next() {
+                Some('n') => result.push('\n'),
+                Some('t') => result.push('\t'),
+                Some('0') => result.push('\0'),
+                Some('\\') => result.push('\\'),
+                Some(other) => {
+                    result.push('\\');
+                    result.push(other);
+                }
+                None => result.push('\\'),
+            },
+            _ => result.push(c),
+        }
+    }
+    Ok(result)
+}
+
+// rwx の形式のパーミッション (lsr の format_mode と同じ)
+#[cfg(unix)]
+fn format_mode(mode: u32) -> String {
+    let fmt = |m: usize| -> &str { ["---", "--x", "-w-", "-wx", "r--", "r-x", "rw-", "rwx"][m] };
+
+    let user_mode = (mode as usize >> 6) & 0o7;
+    let group_mode = (mode as usize >> 3) & 0o7;
+    let other_mode = mode as usize & 0o7;
+    format!("{}{}{}", fmt(user_mode), fmt(group_mode), fmt(other_mode))
+}
+
+// --delete でファイルはそのまま、ディレクトリは空のときだけ消し、消せたかを返す
+// 中身が残っているディレクトリや消せなかったものはエラーを表示して続ける
+// find と同じく、カレントディレクトリ (.) は消さない
+fn delete_entry(entry: &DirEntry) -> bool {
+    let path = entry.path();
+    if path == Path::new(".") {
+        return true;
+    }
+    let res = if entry.file_type().is_dir() {
+        fs::remove_dir(path)
+    } else {
+        fs::remove_file(path)
+    };
+    match res {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("findr: cannot delete {}: {}", path.display(), e);
+            false
+        }
+    }
+}
+
+// {} + で 1 回に渡す引数の長さの上限 (xargs の既定値と同じ 128 KiB)
+// 環境変数の分も ARG_MAX に含まれるので、実際の上限より小さくしておく
+const EXEC_ARG_MAX: usize = 128 * 1024;
+
+// {} + で実行するまでためておくパス
+#[derive(Debug, Default)]
+struct ExecBatch {
+    paths: Vec<PathBuf>,
+    // コマンドとためたパスの引数の長さの合計
+    len: usize,
+}
+
+impl ExecBatch {
+    // path を加えると引数の長さの合計が EXEC_ARG_MAX を超えるときは、
+    // それまでにためたパスを取り出して返してから加える
+    fn push(&mut self, command: &[String], path: PathBuf) -> Option<Vec<PathBuf>> {
+        // 引数 1 つごとに、文字列の終わりの \0 とそれを指すポインタの分も数える
+        let arg_len = |len: usize| len + 1 + std::mem::size_of::<usize>();
+        let path_len = arg_len(path.as_os_str().len());
+        let full =
+            (!self.paths.is_empty() && self.len + path_len > EXEC_ARG_MAX).then(|| self.take());
+        if self.paths.is_empty() {
+            self.len = command.iter().map(|arg| arg_len(arg.len())).sum();
+        }
+        self.len += path_len;
+        self.paths.push(path);
+        full
+    }
+
+    // ためたパスをすべて取り出す
+    fn take(&mut self) -> Vec<PathBuf> {
+        self.len = 0;
+        std::mem::take(&mut self.paths)
+    }
+}
+
+// exec_command と同じだが、ためておいたパスを受け取る
+fn exec_paths(out: &mut impl Write, command: &[String], paths: &[PathBuf]) -> MyResult<bool> {
+    let paths: Vec<&Path> = paths.iter().map(PathBuf::as_path).collect();
+    exec_command(out, command, &paths)
+}
+
+// command の引数の {} を paths に置き換えて実行し、成功したかを返す
+// 引数の一部に {} があるとき (sh -c 'echo X{}X' など) はその部分だけを置き換える
+fn exec_command(out: &mut impl Write, command: &[String], paths: &[&Path]) -> MyResult<bool> {
+    // 子プロセスの出力と findr の出力の順序が入れ替わらないようにする
+    out.flush()?;
+
+    let mut cmd = Command::new(&command[0]);
+    for arg in &command[1..] {
+        match arg.as_str() {
+            "{}" => cmd.args(paths),
+            _ if arg.contains("{}") => cmd.args(
+                paths
+                    .iter()
+                    .map(|path| arg.replace("{}", &path.to_string_lossy())),
+            ),
+            _ => cmd.arg(arg),
+        };
+    }
+    match cmd.status() {
+        Ok(status) => Ok(status.success()),
+        Err(e) => {
+            eprintln!("findr: {}: {}", command[0], e);
+            Ok(false)
+        }
+    }
+}
+
+// types のどれかに当てはまるか
+fn has_type(types: &[EntryType], entry: &DirEntry) -> bool {
+    (types.contains(&Dir) && entry.file_type().is_dir())
+        || (types.contains(&File) && entry.file_type().is_file())
+        || (types.contains(&Link) && entry.file_type().is_symlink())
+}
+
+// find -xtype と同じく、シンボリックリンクはリンク先の種類で判定する
+// リンク先をたどれない (壊れた) リンクはリンクとして扱う
+fn has_xtype(types: &[EntryType], entry: &DirEntry) -> bool {
+    if !entry.path_is_symlink() {
+        return has_type(types, entry);
+    }
+    match fs::metadata(entry.path()) {
+        Ok(metadata) => {
+            (types.contains(&Dir) && metadata.is_dir())
+                || (types.contains(&File) && metadata.is_file())
+        }
+        Err(_) => types.contains(&Link),
+    }
+}
+
+// names のどれかにファイル名がマッチするか
+// UTF-8 として不正なファイル名は置換文字に変えてマッチさせる
+fn has_name(names: &[Regex], entry: &DirEntry) -> bool {
+    names.iter().any(|regex| match entry.path().file_name() {
+        Some(name) => regex.is_match(&name.to_string_lossy()),
+        None => false,
+    })
+}
+
+// ファイル名が . で始まるか
+fn is_hidden(entry: &DirEntry) -> bool {
+    entry.file_name().to_string_lossy().starts_with('.')
+}
+
+// names のすべてにファイル名がマッチするか
+fn has_all_names(names: &[Regex], entry: &DirEntry) -> bool {
+    match entry.path().file_name() {
+        Some(name) => {
+            let name = name.to_string_lossy();
+            names.iter().all(|regex| regex.is_match(&name))
+        }
+        None => false,
+    }
+}
+
+// --print0 のときに名前に改行を含むパスを区別できるよう、パスはバイト列のまま書き込む
+fn write_path(out: &mut impl Write, path: &Path, terminator: u8) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        out.write_all(path.as_os_str().as_bytes())?;
+    }
+    #[cfg(not(unix))]
+    out.write_all(path.display().to_string().as_bytes())?;
+    out.write_all(&[terminator])
+}
+
+// パーミッションのビット (Unix 以外では取得できない)
+fn file_mode(metadata: &fs::Metadata) -> Option<u32> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        Some(metadata.mode())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        None
+    }
+}
+
+// 所有者の uid と gid (Unix 以外では取得できない)
+fn file_owner(metadata: &fs::Metadata) -> Option<(u32, u32)> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        Some((metadata.uid(), metadata.gid()))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        None
+    }
+}
+
+// ファイルがあるデバイスの ID (Unix 以外では取得できない)
+fn file_device(metadata: &fs::Metadata) -> Option<u64> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        Some(metadata.dev())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        None
+    }
+}
+
+// ファイルを識別するデバイスと inode の組 (Unix 以外では取得できない)
+fn file_id(metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        Some((metadata.dev(), metadata.ino()))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        None
+    }
+}
+
+// ハードリンクの数 (Unix 以外では取得できない)
+fn file_nlink(metadata: &fs::Metadata) -> Option<u64> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        Some(metadata.nlink())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        None
+    }
+}
+
+// --readable, --writable, --executable で調べる操作
+#[derive(Debug, Clone, Copy)]
+enum Access {
+    Read,
+    Write,
+    Execute,
+}
+
+// 現在のユーザーが path に対して access の操作をできるか
+#[cfg(unix)]
+fn is_accessible(path: &Path, access: Access) -> bool {
+    use nix::unistd::AccessFlags;
+
+    let flags = match access {
+        Access::Read => AccessFlags::R_OK,
+        Access::Write => AccessFlags::W_OK,
+        Access::Execute => AccessFlags::X_OK,
+    };
+    nix::unistd::access(path, flags).is_ok()
+}
+
+// access(2) がないので、読み取り専用の属性だけで判断する
+#[cfg(not(unix))]
+fn is_accessible(path: &Path, access: Access) -> bool {
+    match (fs::metadata(path), access) {
+        (Ok(_), Access::Read) => true,
+        (Ok(metadata), Access::Write) => !metadata.permissions().readonly(),
+        _ => false,
+    }
+}
+
+// どちらのデバイスもわかっていて異なるときだけ true を返す
+fn is_other_device(root_dev: Option<u64>, dev: Option<u64>) -> bool {
+    matches!((root_dev, dev), (Some(root_dev), Some(dev)) if root_dev != dev)
+}
+
+// 長さ 0 の通常のファイルと、中に何もないディレクトリ
+fn is_empty(entry: &DirEntry) -> bool {
+    if entry.file_type().is_file() {
+        entry.metadata().is_ok_and(|m| m.len() == 0)
+    } else if entry.file_type().is_dir() {
+        fs::read_dir(entry.path()).is_ok_and(|mut entries| entries.next().is_none())
+    } else {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        apply_printf, find_entries, format_long, glob_to_regex, is_other_device, parse_date,
+        parse_depth, parse_entry_type, parse_group, parse_links, parse_mtime, parse_perm,
+        parse_size, parse_user, run_with_writer, walk, Cmp, Config, EntryType, ExecBatch,
+        FilterGroup, MtimeFilter, PermFilter, SizeFilter,
+    };
+    use regex::Regex;
+    use std::{
+        fs,
+        path::PathBuf,
+        time::{Duration, SystemTime},
+    };
+
+    fn config(paths: &[&str]) -> Config {
+        Config {
+            paths: paths.iter().map(|path| path.to_string()).collect(),
+            prune: vec![],
+            filters: vec![FilterGroup::default()],
+            max_depth: None,
+            min_depth: None,
+            print0: false,
+            sort: false,
+            follow: false,
+            depth_first: false,
+            quit: false,
+            xdev: false,
+            no_hidden: false,
+            count: false,
+            ls: false,
+            printf: None,
+            exec: None,
+            exec_batch: false,
+            delete: false,
+        }
+    }
+
+    #[test]
+    fn test_parse_depth() {
+        assert_eq!(parse_depth("0").unwrap(), 0);
+        assert_eq!(parse_depth("3").unwrap(), 3);
+
+        let res = parse_depth("-1");
+        assert!(res.is_err());
+        assert_eq!(res.unwrap_err().to_string(), "-1");
+
+        assert!(parse_depth("foo").is_err());
+    }
+
+    #[test]
+    fn test_parse_size() {
+        let res = parse_size("+10k");
+        assert_eq!(
+            res.unwrap(),
+            SizeFilter {
+                cmp: Cmp::Greater,
+                amount: 10,
+                unit: 1024,
+            }
+        );
+
+        let res = parse_size("-1M");
+        assert_eq!(
+            res.unwrap(),
+            SizeFilter {
+                cmp: Cmp::Less,
+                amount: 1,
+                unit: 1024 * 1024,
+            }
+        );
+
+        let res = parse_size("100c");
+        assert_eq!(
+            res.unwrap(),
+            SizeFilter {
+                cmp: Cmp::Equal,
+                amount: 100,
+                unit: 1,
+            }
+        );
+
+        // 単位がなければ 512 バイトのブロック
+        let res = parse_size("3");
+        assert_eq!(
+            res.unwrap(),
+            SizeFilter {
+                cmp: Cmp::Equal,
+                amount: 3,
+                unit: 512,
+            }
+        );
+
+        for bad in ["", "+", "k", "10x", "1.5k", "++1", "+-1", "１k"] {
+            let res = parse_size(bad);
+            assert!(res.is_err(), "{}", bad);
+            assert_eq!(res.unwrap_err().to_string(), bad);
+        }
+    }
+
+    #[test]
+    fn test_size_filter_is_match() {
+        // サイズは単位に切り上げてから比較する
+        let filter = parse_size("1k").unwrap();
+        assert!(!filter.is_match(0));
+        assert!(filter.is_match(1));
+        assert!(filter.is_match(1024));
+        assert!(!filter.is_match(1025));
+
+        let filter = parse_size("+1k").unwrap();
+        assert!(!filter.is_match(1024));
+        assert!(filter.is_match(1025));
+
+        let filter = parse_size("-1M").unwrap();
+        assert!(filter.is_match(0));
+        assert!(!filter.is_match(1));
+    }
+
+    #[test]
+    fn test_print0() {
+        let config = Config {
+            print0: true,
+            ..config(&["tests/inputs/a/b"])
+        };
+        let mut out = vec![];
+        run_with_writer(config, &mut out).unwrap();
+        assert_eq!(out.last(), Some(&b'\0'));
+        assert!(!out.contains(&b'\n'));
+
+        let mut paths: Vec<_> = out[..out.len() - 1].split(|&b| b == b'\0').collect();
+        paths.sort();
+        let expected: Vec<&[u8]> = vec![
+            b"tests/inputs/a/b",
+            b"tests/inputs/a/b/b.csv",
+            b"tests/inputs/a/b/c",
+            b"tests/inputs/a/b/c/c.mp3",
+        ];
+        assert_eq!(paths, expected);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_print0_newline_in_name() {
+        let dir = std::env::temp_dir().join(format!("findr-print0-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("new\nline.txt"), "").unwrap();
+
+        let dirname = dir.to_str().unwrap();
+        let config = Config {
+            print0: true,
+            ..config(&[dirname])
+        };
+        let mut out = vec![];
+        let res = run_with_writer(config, &mut out);
+        fs::remove_dir_all(&dir).unwrap();
+        res.unwrap();
+
+        let expected = format!("{}\0{}/new\nline.txt\0", dirname, dirname);
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_path_pattern_non_utf8() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        let dir = std::env::temp_dir().join(format!("findr-path-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(OsStr::from_bytes(b"bad\xff.txt")), "").unwrap();
+
+        let config = Config {
+            filters: vec![FilterGroup {
+                path_patterns: vec![Regex::new("bad.*[.]txt$").unwrap()],
+                ..FilterGroup::default()
+            }],
+            ..config(&[dir.to_str().unwrap()])
+        };
+        let mut out = vec![];
+        let res = run_with_writer(config, &mut out);
+        fs::remove_dir_all(&dir).unwrap();
+        res.unwrap();
+
+        // 不正なバイトはそのまま出力される
+        assert!(out.ends_with(b"/bad\xff.txt\n"));
+    }
+
+    #[test]
+    fn test_sort() {
+        let config = Config {
+            sort: true,
+            ..config(&["tests/inputs/d", "tests/inputs/a"])
+        };
+        let mut out = vec![];
+        run_with_writer(config, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let paths: Vec<_> = out.lines().map(std::path::Path::new).collect();
+        assert_eq!(paths.len(), 12);
+        assert!(paths.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(paths[0], std::path::Path::new("tests/inputs/a"));
+    }
+
+    #[test]
+    fn test_parse_entry_type() {
+        assert_eq!(parse_entry_type("f").unwrap(), EntryType::File);
+        assert_eq!(parse_entry_type("d").unwrap(), EntryType::Dir);
+        assert_eq!(parse_entry_type("l").unwrap(), EntryType::Link);
+
+        let res = parse_entry_type("x");
+        assert!(res.is_err());
+        assert_eq!(res.unwrap_err().to_string(), "Invalid type \"x\"");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_name_non_utf8() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        let dir = std::env::temp_dir().join(format!("findr-name-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(OsStr::from_bytes(b"bad\xff.txt")), "").unwrap();
+        fs::write(dir.join("good.txt"), "").unwrap();
+
+        let config = Config {
+            filters: vec![FilterGroup {
+                names: vec![Regex::new("[.]txt$").unwrap()],
+                ..FilterGroup::default()
+            }],
+            ..config(&[dir.to_str().unwrap()])
+        };
+        let mut out = vec![];
+        let res = run_with_writer(config, &mut out);
+        fs::remove_dir_all(&dir).unwrap();
+        res.unwrap();
+
+        let mut names: Vec<_> = out
+            .split(|&b| b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| line.rsplit(|&b| b == b'/').next().unwrap())
+            .collect();
+        names.sort();
+        assert_eq!(names, vec![&b"bad\xff.txt"[..], &b"good.txt"[..]]);
+    }
+
+    #[test]
+    fn test_parse_date() {
+        use chrono::{DateTime, Local};
+
+        let local = |time: SystemTime| DateTime::<Local>::from(time).naive_local().to_string();
+        assert_eq!(
+            local(parse_date("2024-03-01").unwrap()),
+            "2024-03-01 00:00:00"
+        );
+        assert_eq!(
+            local(parse_date("2024-03-01 12:34").unwrap()),
+            "2024-03-01 12:34:00"
+        );
+        assert_eq!(
+            local(parse_date("2024-03-01 12:34:56").unwrap()),
+            "2024-03-01 12:34:56"
+        );
+
+        for bad in ["2024-02-30", "2024/03/01", "yesterday", ""] {
+            let res = parse_date(bad);
+            assert!(res.is_err());
+            assert_eq!(res.unwrap_err().to_string(), bad);
+        }
+    }
+
+    #[test]
+    fn test_parse_links() {
+        assert_eq!(parse_links("+1").unwrap(), (Cmp::Greater, 1));
+        assert_eq!(parse_links("-2").unwrap(), (Cmp::Less, 2));
+        assert_eq!(parse_links("1").unwrap(), (Cmp::Equal, 1));
+        for bad in ["", "+", "1x", "+-1"] {
+            assert!(parse_links(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_parse_mtime() {
+        let res = parse_mtime("+7");
+        assert_eq!(
+            res.unwrap(),
+            MtimeFilter {
+                cmp: Cmp::Greater,
+                days: 7,
+            }
+        );
+
+        let res = parse_mtime("-1");
+        assert_eq!(
+            res.unwrap(),
+            MtimeFilter {
+                cmp: Cmp::Less,
+                days: 1,
+            }
+        );
+
+        let res = parse_mtime("0");
+        assert_eq!(
+            res.unwrap(),
+            MtimeFilter {
+                cmp: Cmp::Equal,
+                days: 0,
+            }
+        );
+
+        for bad in ["", "+", "1d", "+-1", "1.5"] {
+            let res = parse_mtime(bad);
+            assert!(res.is_err(), "{}", bad);
+            assert_eq!(res.unwrap_err().to_string(), bad);
+        }
+    }
+
+    #[test]
+    fn test_mtime_filter_is_match() {
+        let day = Duration::from_secs(24 * 60 * 60);
+        let now = SystemTime::now();
+
+        // 経過日数は切り捨てるので、1 日半前は 1 日前として扱う
+        let modified = now - day * 3 / 2;
+        assert!(parse_mtime("1").unwrap().is_match(now, modified));
+        assert!(parse_mtime("+0").unwrap().is_match(now, modified));
+        assert!(!parse_mtime("+1").unwrap().is_match(now, modified));
+        assert!(parse_mtime("-2").unwrap().is_match(now, modified));
+        assert!(!parse_mtime("-1").unwrap().is_match(now, modified));
+
+        // 未来の時刻は 0 日前
+        assert!(parse_mtime("0").unwrap().is_match(now, now + day));
+    }
+
+    #[test]
+    fn test_find_entries() {
+        use std::path::PathBuf;
+
+        let csv = Config {
+            filters: vec![FilterGroup {
+                names: vec![Regex::new(r"\.csv$").unwrap()],
+                entry_types: vec![EntryType::File],
+                ..FilterGroup::default()
+            }],
+            sort: true,
+            ..config(&["tests/inputs"])
+        };
+        // tests/inputs/d/b.csv はシンボリックリンクなので含まれない
+        let found = find_entries(&csv).unwrap();
+        assert_eq!(
+            found,
+            vec![
+                PathBuf::from("tests/inputs/a/b/b.csv"),
+                PathBuf::from("tests/inputs/g.csv"),
+            ]
+        );
+
+        let dir = Config {
+            filters: vec![FilterGroup {
+                names: vec![Regex::new("a").unwrap()],
+                entry_types: vec![EntryType::Dir],
+                ..FilterGroup::default()
+            }],
+            ..config(&["tests/inputs"])
+        };
+        let found = find_entries(&dir).unwrap();
+        assert_eq!(found, vec![PathBuf::from("tests/inputs/a")]);
+
+        // どちらかの組を満たせばよい
+        let either = Config {
+            filters: vec![
+                FilterGroup {
+                    names: vec![Regex::new(r"\.csv$").unwrap()],
+                    entry_types: vec![EntryType::File],
+                    ..FilterGroup::default()
+                },
+                FilterGroup {
+                    names: vec![Regex::new("a").unwrap()],
+                    entry_types: vec![EntryType::Dir],
+                    ..FilterGroup::default()
+                },
+            ],
+            sort: true,
+            ..config(&["tests/inputs"])
+        };
+        let found = find_entries(&either).unwrap();
+        assert_eq!(
+            found,
+            vec![
+                PathBuf::from("tests/inputs/a"),
+                PathBuf::from("tests/inputs/a/b/b.csv"),
+                PathBuf::from("tests/inputs/g.csv"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_glob_to_regex() {
+        let re = glob_to_regex("*.txt");
+        assert!(re.is_match("a.txt"));
+        assert!(re.is_match(".txt"));
+        assert!(!re.is_match("a.txt.bak"));
+        assert!(!re.is_match("atxt"));
+
+        let re = glob_to_regex("foo?.rs");
+        assert!(re.is_match("foo1.rs"));
+        assert!(!re.is_match("foo.rs"));
+        assert!(!re.is_match("foo12.rs"));
+
+        // . はリテラルとして扱う
+        let re = glob_to_regex("a.b");
+        assert!(re.is_match("a.b"));
+        assert!(!re.is_match("axb"));
+
+        let re = glob_to_regex("(a)+");
+        assert!(re.is_match("(a)+"));
+        assert!(!re.is_match("aa"));
+    }
+
+    #[test]
+    fn test_parse_perm() {
+        assert_eq!(
+            parse_perm("644").unwrap(),
+            PermFilter {
+                mode: 0o644,
+                all: false,
+            }
+        );
+        assert_eq!(
+            parse_perm("-755").unwrap(),
+            PermFilter {
+                mode: 0o755,
+                all: true,
+            }
+        );
+        assert_eq!(parse_perm("4755").unwrap().mode, 0o4755);
+
+        for bad in ["", "-", "8", "64a", "+644", "17777"] {
+            let res = parse_perm(bad);
+            assert!(res.is_err());
+            assert_eq!(res.unwrap_err().to_string(), bad);
+        }
+    }
+
+    #[test]
+    fn test_perm_filter_is_match() {
+        let exact = parse_perm("644").unwrap();
+        assert!(exact.is_match(0o644));
+        // ファイルの種類を表す上位のビットは無視する
+        assert!(exact.is_match(0o100644));
+        assert!(!exact.is_match(0o664));
+        assert!(!exact.is_match(0o600));
+
+        let all = parse_perm("-755").unwrap();
+        assert!(all.is_match(0o755));
+        assert!(all.is_match(0o777));
+        assert!(all.is_match(0o4755));
+        assert!(!all.is_match(0o744));
+        assert!(!all.is_match(0o644));
+    }
+
+    #[test]
+    fn test_parse_user_group() {
+        assert_eq!(parse_user("0").unwrap(), 0);
+        assert_eq!(parse_user("1000").unwrap(), 1000);
+        assert_eq!(parse_group("1000").unwrap(), 1000);
+
+        // u32 に収まらない ID
+        assert!(parse_user("4294967296").is_err());
+
+        let res = parse_user("no-such-user-findr");
+        assert!(res.is_err());
+        assert_eq!(res.unwrap_err().to_string(), "no-such-user-findr");
+        assert!(parse_group("no-such-group-findr").is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_parse_user_name() {
+        let uid = users::get_current_uid();
+        if let Some(user) = users::get_user_by_uid(uid) {
+            assert_eq!(parse_user(&user.name().to_string_lossy()).unwrap(), uid);
+        }
+        let gid = users::get_current_gid();
+        if let Some(group) = users::get_group_by_gid(gid) {
+            assert_eq!(parse_group(&group.name().to_string_lossy()).unwrap(), gid);
+        }
+    }
+
+    #[test]
+    fn test_depth_first() {
+        let config = Config {
+            depth_first: true,
+            ..config(&["tests/inputs/a"])
+        };
+        let mut out = vec![];
+        run_with_writer(config, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let paths: Vec<_> = out.lines().map(std::path::Path::new).collect();
+        let position = |path: &str| {
+            paths
+                .iter()
+                .position(|p| *p == std::path::Path::new(path))
+                .unwrap()
+        };
+        assert_eq!(paths.len(), 6);
+        assert!(position("tests/inputs/a/a.txt") < position("tests/inputs/a"));
+        assert!(position("tests/inputs/a/b/c/c.mp3") < position("tests/inputs/a/b/c"));
+        assert!(position("tests/inputs/a/b/c") < position("tests/inputs/a/b"));
+        assert_eq!(paths.last(), Some(&std::path::Path::new("tests/inputs/a")));
+    }
+
+    #[test]
+    fn test_is_other_device() {
+        assert!(!is_other_device(Some(1), Some(1)));
+        assert!(is_other_device(Some(1), Some(2)));
+        // デバイスがわからないときは飛ばさない
+        assert!(!is_other_device(None, Some(2)));
+        assert!(!is_other_device(Some(1), None));
+        assert!(!is_other_device(None, None));
+    }
+
+    #[test]
+    fn test_xdev_same_filesystem() {
+        let xdev = Config {
+            xdev: true,
+            sort: true,
+            ..config(&["tests/inputs"])
+        };
+        let all = Config {
+            sort: true,
+            ..config(&["tests/inputs"])
+        };
+        assert_eq!(find_entries(&xdev).unwrap(), find_entries(&all).unwrap());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_format_long() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("findr-ls-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("file.txt");
+        fs::write(&path, "hello").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+
+        let entry = walkdir::WalkDir::new(&path)
+            .into_iter()
+            .next()
+            .unwrap()
+            .unwrap();
+        let line = format_long(&entry);
+        fs::remove_dir_all(&dir).unwrap();
+
+        let line = line.unwrap();
+        let fields: Vec<_> = line.split_whitespace().collect();
+        assert!(fields[0].parse::<u64>().is_ok());
+        assert_eq!(fields[2], "-rw-r-----");
+        assert_eq!(fields[3], "1");
+        assert_eq!(fields[6], "5");
+        assert!(line.ends_with(&path.display().to_string()));
+    }
+
+    #[test]
+    fn test_apply_printf() {
+        let entry = |path: &str| {
+            walkdir::WalkDir::new(path)
+                .into_iter()
+                .next()
+                .unwrap()
+                .unwrap()
+        };
+        let file = entry("tests/inputs/a/a.txt");
+        assert_eq!(
+            apply_printf("%p\\n", &file).unwrap(),
+            "tests/inputs/a/a.txt\n"
+        );
+        assert_eq!(apply_printf("%f %s\\n", &file).unwrap(), "a.txt 2\n");
+        assert_eq!(apply_printf("%y\\t%f", &file).unwrap(), "f\ta.txt");
+        assert_eq!(apply_printf("%y", &entry("tests/inputs/a")).unwrap(), "d");
+
+        // 知らない指示子やエスケープ、末尾の % はそのまま
+        assert_eq!(apply_printf("%z %q\\x", &file).unwrap(), "%z %q\\x");
+        assert_eq!(apply_printf("100%% %", &file).unwrap(), "100% %");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_apply_printf_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("findr-printf-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("file.txt");
+        fs::write(&path, "").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+
+        let entry = walkdir::WalkDir::new(&path)
+            .into_iter()
+            .next()
+            .unwrap()
+            .unwrap();
+        let res = apply_printf("%m", &entry);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(res.unwrap(), "640");
+    }
+
+    #[test]
+    fn test_exec_batch() {
+        let command = vec!["echo".to_string(), "{}".to_string()];
+        let mut batch = ExecBatch::default();
+        assert!(batch.push(&command, PathBuf::from("a")).is_none());
+        assert!(batch.push(&command, PathBuf::from("b")).is_none());
+        assert_eq!(batch.take(), [PathBuf::from("a"), PathBuf::from("b")]);
+        assert!(batch.take().is_empty());
+
+        // 1 つ 1 KiB のパスを 300 個ためると 128 KiB を超えるので分ける
+        let mut chunks = vec![];
+        for i in 0..300 {
+            chunks.extend(batch.push(&command, PathBuf::from(format!("{:01024}", i))));
+        }
+        chunks.push(batch.take());
+        assert!(chunks.len() > 1);
+        assert_eq!(chunks.iter().map(Vec::len).sum::<usize>(), 300);
+        for chunk in chunks {
+            let len: usize = chunk.iter().map(|path| path.as_os_str().len() + 9).sum();
+            assert!(len < 128 * 1024);
+        }
+    }
+
+    #[test]
+    fn test_walk_streams() {
+        // 見つけたそばから渡すので、途中で止めればそれ以上は走査しない
+        let mut seen = 0;
+        let res = walk(&config(&["tests/inputs"]), &mut |_| {
+            seen += 1;
+            Err(From::from("stop"))
+        });
+        assert!(res.is_err());
+        assert_eq!(seen, 1);
+    }
+}
@@ -3,7 +3,12 @@ use assert_cmd::Command;
 use predicates::prelude::*;
 use pretty_assertions::assert_eq;
 use rand::{distributions::Alphanumeric, Rng};
-use std::{borrow::Cow, fs, path::Path};
+use std::{
+    borrow::Cow,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
 
 const PRG: &str = "findr";
 
@@ -60,14 +65,14 @@ fn dies_bad_type() -> Result<()> {
 
 // --------------------------------------------------
 #[cfg(windows)]
-fn format_file_name(expected_file: &str) -> Cow<str> {
+fn format_file_name(expected_file: &str) -> Cow<'_, str> {
     // Equivalent to: Cow::Owned(format!("{}.windows", expected_file))
     format!("{}.windows", expected_file).into()
 }
 
 // --------------------------------------------------
 #[cfg(not(windows))]
-fn format_file_name(expected_file: &str) -> Cow<str> {
+fn format_file_name(expected_file: &str) -> Cow<'_, str> {
     // Equivalent to: Cow::Borrowed(expected_file)
     expected_file.into()
 }
@@ -240,6 +245,54 @@ fn name_csv_mp3() -> Result<()> {
     )
 }
 
+// --------------------------------------------------
+#[test]
+fn name_b_csv() -> Result<()> {
+    // 既定ではどれか 1 つにマッチすればよい
+    run(
+        &["tests/inputs", "-n", "b", "-n", "csv"],
+        "tests/expected/name_b_csv.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn name_all_b_csv() -> Result<()> {
+    run(
+        &["tests/inputs", "--name-all", "-n", "b", "-n", "csv"],
+        "tests/expected/name_all_b_csv.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn name_all_iname() -> Result<()> {
+    // --iname もすべてにマッチする必要がある
+    run(
+        &["tests/inputs", "--name-all", "-n", "b", "--iname", "CSV"],
+        "tests/expected/name_all_b_csv.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn glob_csv_mp3() -> Result<()> {
+    run(
+        &["tests/inputs", "--glob", "-n", "*.csv", "-n", "*.mp3"],
+        "tests/expected/name_csv_mp3.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn glob_a() -> Result<()> {
+    // グロブはファイル名全体にマッチする
+    run(
+        &["tests/inputs", "--glob", "-n", "a"],
+        "tests/expected/glob_a.txt",
+    )
+}
+
 // --------------------------------------------------
 #[test]
 fn name_txt_path_a_d() -> Result<()> {
@@ -251,32 +304,915 @@ fn name_txt_path_a_d() -> Result<()> {
 
 // --------------------------------------------------
 #[test]
-fn name_a() -> Result<()> {
-    run(&["tests/inputs", "-n", "a"], "tests/expected/name_a.txt")
+fn name_a() -> Result<()> {
+    run(&["tests/inputs", "-n", "a"], "tests/expected/name_a.txt")
+}
+
+// --------------------------------------------------
+#[test]
+fn type_f_name_a() -> Result<()> {
+    run(
+        &["tests/inputs", "-t", "f", "-n", "a"],
+        "tests/expected/type_f_name_a.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn type_d_name_a() -> Result<()> {
+    run(
+        &["tests/inputs", "--type", "d", "--name", "a"],
+        "tests/expected/type_d_name_a.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn iname_a() -> Result<()> {
+    run(
+        &["tests/inputs", "--iname", "^A[.]TXT$"],
+        "tests/expected/iname_a.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn iname_csv_name_mp3() -> Result<()> {
+    run(
+        &["tests/inputs", "--iname", "[.]CSV$", "-n", ".*[.]mp3"],
+        "tests/expected/name_csv_mp3.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_iname() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--iname", "*.csv"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid --iname \"*.csv\""));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn path_pattern_d() -> Result<()> {
+    run(
+        &["tests/inputs", "--path", "tests/inputs/d/.*"],
+        "tests/expected/path_pattern_d.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn path_pattern_type_f_name_txt() -> Result<()> {
+    run(
+        &["tests/inputs", "--path", "/[ad]/", "-t", "f", "-n", "[.]txt$"],
+        "tests/expected/path_pattern_type_f_name_txt.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_path_pattern() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--path", "*.csv"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid --path \"*.csv\""));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn not_name_txt() -> Result<()> {
+    run(
+        &["tests/inputs", "--not-name", "[.]txt$"],
+        "tests/expected/not_name_txt.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn not_type_d_not_name_csv() -> Result<()> {
+    run(
+        &["tests/inputs", "--not-type", "d", "--not-name", "[.]csv$"],
+        "tests/expected/not_type_d_not_name_csv.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn type_f_l_not_type_l() -> Result<()> {
+    run(
+        &["tests/inputs", "-t", "f", "l", "--not-type", "l"],
+        "tests/expected/type_f.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn path_g() -> Result<()> {
+    run(&["tests/inputs/g.csv"], "tests/expected/path_g.txt")
+}
+
+// --------------------------------------------------
+#[test]
+fn max_depth_0() -> Result<()> {
+    run(
+        &["tests/inputs", "--max-depth", "0"],
+        "tests/expected/max_depth_0.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn max_depth_1() -> Result<()> {
+    run(
+        &["tests/inputs", "--max-depth", "1"],
+        "tests/expected/max_depth_1.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn min_depth_3() -> Result<()> {
+    run(
+        &["tests/inputs", "--min-depth", "3"],
+        "tests/expected/min_depth_3.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn min_depth_2_max_depth_2() -> Result<()> {
+    run(
+        &["tests/inputs", "--min-depth", "2", "--max-depth", "2"],
+        "tests/expected/min_depth_2_max_depth_2.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_max_depth() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--max-depth", "x"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid --max-depth \"x\""));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn size_plus_1k() -> Result<()> {
+    run(
+        &["tests/sizes", "--size", "+1k"],
+        "tests/expected/size_plus_1k.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn size_minus_2k() -> Result<()> {
+    run(
+        &["tests/sizes", "--size", "-2k"],
+        "tests/expected/size_minus_2k.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn size_1024c() -> Result<()> {
+    run(
+        &["tests/sizes", "--size", "1024c"],
+        "tests/expected/size_1024c.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn prune_a() -> Result<()> {
+    run(
+        &["tests/inputs", "--prune", "^a$"],
+        "tests/expected/prune_a.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn prune_b_e() -> Result<()> {
+    run(
+        &["tests/inputs", "--prune", "^b$", "--prune", "^e$"],
+        "tests/expected/prune_b_e.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_prune() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--prune", "*.csv"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid --prune \"*.csv\""));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn count_type_f() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-t", "f", "--count"])
+        .assert()
+        .success()
+        .stdout("8\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn count_no_match() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-n", "[.]rs$", "--count"])
+        .assert()
+        .success()
+        .stdout("0\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_count_with_exec() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "--count", "--exec", "echo", "{}", ";"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn ls() -> Result<()> {
+    let output = Command::cargo_bin(PRG)?
+        .args(["tests/inputs/a", "-t", "f", "--ls"])
+        .output()?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout)?;
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 3);
+    for line in lines {
+        let perms = line.split_whitespace().nth(2).unwrap();
+        assert!(perms.starts_with("-rw"));
+        assert!(
+            line.ends_with("tests/inputs/a/a.txt")
+                || line.ends_with("tests/inputs/a/b/b.csv")
+                || line.ends_with("tests/inputs/a/b/c/c.mp3")
+        );
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn printf() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs/a", "-t", "f", "--sort", "--printf", "%f %s\\n"])
+        .assert()
+        .success()
+        .stdout("a.txt 2\nb.csv 2\nc.mp3 2\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn printf_no_newline() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs/f", "--sort", "--printf", "[%y:%f]"])
+        .assert()
+        .success()
+        .stdout("[d:f][f:f.txt]");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn quit() -> Result<()> {
+    let output = Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-n", "[.]txt$", "--quit"])
+        .output()?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout)?;
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 1);
+    assert!(lines[0].ends_with(".txt"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn quit_no_match() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-n", "[.]rs$", "--quit"])
+        .assert()
+        .success()
+        .stdout("");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_size() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--size", "10x"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid --size \"10x\""));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn empty() -> Result<()> {
+    // 空のディレクトリは git で管理できないのでここで作る
+    let dirname = "tests/sizes/empty_dir";
+    if !Path::new(dirname).exists() {
+        fs::create_dir(dirname)?;
+    }
+
+    let res = run(&["tests/sizes", "--empty"], "tests/expected/empty.txt");
+    fs::remove_dir(dirname)?;
+    res
+}
+
+// --------------------------------------------------
+#[test]
+fn empty_type_f() -> Result<()> {
+    run(
+        &["tests/sizes", "--empty", "--type", "f"],
+        "tests/expected/empty_type_f.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn print0() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs/g.csv", "tests/inputs/f/f.txt", "-0"])
+        .assert()
+        .success()
+        .stdout(format!(
+            "tests/inputs/g.csv\0{}\0",
+            Path::new("tests/inputs/f/f.txt").display()
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn sort() -> Result<()> {
+    // run() は結果を並べ替えてから比べるので、ここでは出力をそのまま比べる
+    let file = format_file_name("tests/expected/sort.txt");
+    let expected = fs::read_to_string(file.as_ref())?;
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs/d", "tests/inputs/a", "--sort"])
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn links() -> Result<()> {
+    run(&["tests/links"], "tests/expected/links.txt")
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn links_type_l() -> Result<()> {
+    run(&["tests/links", "-t", "l"], "tests/expected/links_type_l.txt")
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn follow_links() -> Result<()> {
+    let contents = fs::read_to_string("tests/expected/follow_links.txt")?;
+    let mut expected: Vec<&str> = contents.split('\n').filter(|s| !s.is_empty()).collect();
+    expected.sort();
+
+    // ループしているリンクはエラーを表示するが、検索は続ける
+    let cmd = Command::cargo_bin(PRG)?
+        .args(["tests/links", "-L"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("File system loop found"));
+    let out = cmd.get_output();
+    let stdout = String::from_utf8(out.stdout.clone())?;
+    let mut lines: Vec<&str> = stdout.split('\n').filter(|s| !s.is_empty()).collect();
+    lines.sort();
+
+    assert_eq!(lines, expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn exec_each() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args([
+            "tests/inputs/a/b",
+            "--sort",
+            "--exec",
+            "echo",
+            "found",
+            "{}",
+            ";",
+        ])
+        .assert()
+        .success()
+        .stdout(
+            "found tests/inputs/a/b
+found tests/inputs/a/b/b.csv
+found tests/inputs/a/b/c
+found tests/inputs/a/b/c/c.mp3
+",
+        );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn exec_batch() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs/a/b", "--sort", "--exec", "echo", "{}", "+"])
+        .assert()
+        .success()
+        .stdout(
+            "tests/inputs/a/b tests/inputs/a/b/b.csv tests/inputs/a/b/c \
+             tests/inputs/a/b/c/c.mp3\n",
+        );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn exec_batch_then_filter() -> Result<()> {
+    // {} + のあとの引数は --exec のコマンドではなくフィルタ
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs/a", "--sort", "--exec", "echo", "{}", "+"])
+        .args(["-t", "f"])
+        .assert()
+        .success()
+        .stdout(
+            "tests/inputs/a/a.txt tests/inputs/a/b/b.csv \
+             tests/inputs/a/b/c/c.mp3\n",
+        );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn exec_embedded_braces() -> Result<()> {
+    // 引数の一部にある {} も置き換える
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs/a/b", "-t", "f", "--sort"])
+        .args(["--exec", "sh", "-c", "echo X{}X", ";"])
+        .assert()
+        .success()
+        .stdout("Xtests/inputs/a/b/b.csvX\nXtests/inputs/a/b/c/c.mp3X\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_exec_unterminated() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "--exec", "echo", "{}", "-t", "f"])
+        .assert()
+        .failure()
+        .stderr("--exec: missing terminating ; or {} +\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn exec_fails() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs/a/b", "-t", "f", "--exec", "false", ";"])
+        .assert()
+        .failure()
+        .stdout("");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn exec_bad_command() -> Result<()> {
+    let bad = gen_bad_file();
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs/g.csv", "--exec", &bad, "{}", ";"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(format!("findr: {}: ", bad)));
+    Ok(())
+}
+
+// --------------------------------------------------
+// テストごとに別の一時ディレクトリを作る
+fn tmp_dir(name: &str) -> Result<PathBuf> {
+    let dir = std::env::temp_dir()
+        .join(format!("findr-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+// --------------------------------------------------
+// 更新日時が 10 日前の old.txt と現在の new.txt を置いたディレクトリを作る
+fn make_mtime_dir(name: &str) -> Result<PathBuf> {
+    let dir = tmp_dir(name)?;
+
+    let old = fs::File::create(dir.join("old.txt"))?;
+    let ten_days = Duration::from_secs(10 * 24 * 60 * 60);
+    old.set_modified(SystemTime::now() - ten_days)?;
+    fs::File::create(dir.join("new.txt"))?;
+    Ok(dir)
+}
+
+// --------------------------------------------------
+// 更新日時が 2020-01-02 12:00 (UTC) の dated.txt と、今の new.txt を置いたディレクトリ
+fn make_dated_dir(name: &str) -> Result<PathBuf> {
+    let dir = tmp_dir(name)?;
+
+    let dated = fs::File::create(dir.join("dated.txt"))?;
+    dated.set_modified(
+        SystemTime::UNIX_EPOCH + Duration::from_secs(1_577_966_400),
+    )?;
+    fs::File::create(dir.join("new.txt"))?;
+    Ok(dir)
+}
+
+// --------------------------------------------------
+#[test]
+fn newermt_before() -> Result<()> {
+    // どのタイムゾーンでも 2020-01-01 の 0 時より後
+    run_tmp(
+        make_dated_dir("newermt-before")?,
+        &["--newermt", "2020-01-01"],
+        &["dated.txt", "new.txt"],
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn newermt_after() -> Result<()> {
+    run_tmp(
+        make_dated_dir("newermt-after")?,
+        &["--newermt", "2020-01-03 00:00"],
+        &["new.txt"],
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_newermt() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--newermt", "2020-13-01"])
+        .assert()
+        .failure()
+        .stderr("Invalid --newermt \"2020-13-01\"\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+// 一時ディレクトリ dir の中の通常のファイルを探し、dir を削除する
+// args の DIR は dir のパスに置き換える
+fn run_tmp(dir: PathBuf, args: &[&str], expected: &[&str]) -> Result<()> {
+    let dirname = dir.to_string_lossy().to_string();
+    let args: Vec<String> = args
+        .iter()
+        .map(|arg| arg.replace("DIR", &dirname))
+        .collect();
+    let output = Command::cargo_bin(PRG)?
+        .arg(&dirname)
+        .args(&args)
+        .args(["-t", "f"])
+        .output();
+    fs::remove_dir_all(&dir)?;
+
+    let output = output?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout)?;
+    let mut lines: Vec<&str> =
+        stdout.split('\n').filter(|s| !s.is_empty()).collect();
+    lines.sort();
+    let expected: Vec<String> = expected
+        .iter()
+        .map(|file| dir.join(file).to_string_lossy().to_string())
+        .collect();
+    assert_eq!(lines, expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn mtime_older() -> Result<()> {
+    run_tmp(make_mtime_dir("mtime-older")?, &["--mtime", "+5"], &["old.txt"])
+}
+
+// --------------------------------------------------
+#[test]
+fn mtime_newer() -> Result<()> {
+    run_tmp(make_mtime_dir("mtime-newer")?, &["--mtime", "-5"], &["new.txt"])
+}
+
+// --------------------------------------------------
+#[test]
+fn mtime_exact() -> Result<()> {
+    run_tmp(make_mtime_dir("mtime-exact")?, &["--mtime", "10"], &["old.txt"])
+}
+
+// --------------------------------------------------
+#[test]
+fn newer() -> Result<()> {
+    run_tmp(make_mtime_dir("newer")?, &["--newer", "DIR/old.txt"], &["new.txt"])
+}
+
+// --------------------------------------------------
+// パーミッションが 644, 755, 600 のファイルを置いたディレクトリを作る
+#[cfg(unix)]
+fn make_perm_dir(name: &str) -> Result<PathBuf> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tmp_dir(name)?;
+    let files = [("644.txt", 0o644), ("755.txt", 0o755), ("600.txt", 0o600)];
+    for (file, mode) in files {
+        let path = dir.join(file);
+        fs::File::create(&path)?;
+        fs::set_permissions(&path, fs::Permissions::from_mode(mode))?;
+    }
+    Ok(dir)
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn perm_exact() -> Result<()> {
+    run_tmp(make_perm_dir("perm-exact")?, &["--perm", "644"], &["644.txt"])
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn perm_all() -> Result<()> {
+    run_tmp(make_perm_dir("perm-all")?, &["--perm", "-755"], &["755.txt"])
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn perm_all_user_rw() -> Result<()> {
+    run_tmp(
+        make_perm_dir("perm-all-user-rw")?,
+        &["--perm", "-600"],
+        &["600.txt", "644.txt", "755.txt"],
+    )
+}
+
+// --------------------------------------------------
+// パーミッションが 000, 400, 600, 700 のファイルを置いたディレクトリを作る
+#[cfg(unix)]
+fn make_access_dir(name: &str) -> Result<PathBuf> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tmp_dir(name)?;
+    let files = [
+        ("none.txt", 0o000),
+        ("r.txt", 0o400),
+        ("rw.txt", 0o600),
+        ("rwx.txt", 0o700),
+    ];
+    for (file, mode) in files {
+        let path = dir.join(file);
+        fs::File::create(&path)?;
+        fs::set_permissions(&path, fs::Permissions::from_mode(mode))?;
+    }
+    Ok(dir)
+}
+
+// --------------------------------------------------
+// root はパーミッションに関係なく読み書きできる
+#[cfg(unix)]
+fn is_root() -> bool {
+    users::get_current_uid() == 0
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn readable() -> Result<()> {
+    let expected: &[&str] = if is_root() {
+        &["none.txt", "r.txt", "rw.txt", "rwx.txt"]
+    } else {
+        &["r.txt", "rw.txt", "rwx.txt"]
+    };
+    run_tmp(make_access_dir("readable")?, &["--readable"], expected)
 }
 
 // --------------------------------------------------
 #[test]
-fn type_f_name_a() -> Result<()> {
-    run(
-        &["tests/inputs", "-t", "f", "-n", "a"],
-        "tests/expected/type_f_name_a.txt",
+#[cfg(unix)]
+fn writable() -> Result<()> {
+    let expected: &[&str] = if is_root() {
+        &["none.txt", "r.txt", "rw.txt", "rwx.txt"]
+    } else {
+        &["rw.txt", "rwx.txt"]
+    };
+    run_tmp(make_access_dir("writable")?, &["--writable"], expected)
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn executable() -> Result<()> {
+    // root でも実行できるのは実行ビットが立っているものだけ
+    run_tmp(make_access_dir("executable")?, &["--executable"], &["rwx.txt"])
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn readable_executable() -> Result<()> {
+    run_tmp(
+        make_access_dir("readable-executable")?,
+        &["--readable", "--executable"],
+        &["rwx.txt"],
     )
 }
 
 // --------------------------------------------------
 #[test]
-fn type_d_name_a() -> Result<()> {
-    run(
-        &["tests/inputs", "--type", "d", "--name", "a"],
-        "tests/expected/type_d_name_a.txt",
+fn dies_bad_perm() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--perm", "u+x"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid --perm \"u+x\""));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn user_current() -> Result<()> {
+    let uid = users::get_current_uid().to_string();
+    run_tmp(
+        make_mtime_dir("user-current")?,
+        &["--user", &uid],
+        &["new.txt", "old.txt"],
     )
 }
 
 // --------------------------------------------------
 #[test]
-fn path_g() -> Result<()> {
-    run(&["tests/inputs/g.csv"], "tests/expected/path_g.txt")
+#[cfg(unix)]
+fn user_current_name() -> Result<()> {
+    let Some(user) = users::get_user_by_uid(users::get_current_uid()) else {
+        return Ok(());
+    };
+    let name = user.name().to_string_lossy().to_string();
+    run_tmp(
+        make_mtime_dir("user-current-name")?,
+        &["--user", &name],
+        &["new.txt", "old.txt"],
+    )
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn user_other() -> Result<()> {
+    let uid = (users::get_current_uid() + 1).to_string();
+    run_tmp(make_mtime_dir("user-other")?, &["--user", &uid], &[])
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn group_current() -> Result<()> {
+    let gid = users::get_current_gid().to_string();
+    run_tmp(
+        make_mtime_dir("group-current")?,
+        &["--group", &gid],
+        &["new.txt", "old.txt"],
+    )
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn samefile() -> Result<()> {
+    // old.txt のハードリンクを作る
+    let dir = make_mtime_dir("samefile")?;
+    fs::hard_link(dir.join("old.txt"), dir.join("link.txt"))?;
+    run_tmp(
+        dir,
+        &["--samefile", "DIR/old.txt"],
+        &["link.txt", "old.txt"],
+    )
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn inum() -> Result<()> {
+    use std::os::unix::fs::MetadataExt;
+
+    let inum = fs::symlink_metadata("tests/inputs/a/a.txt")?.ino().to_string();
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "--inum", &inum])
+        .assert()
+        .success()
+        .stdout("tests/inputs/a/a.txt\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_inum() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--inum", "12x"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid --inum \"12x\""));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_samefile() -> Result<()> {
+    let bad = gen_bad_file();
+    Command::cargo_bin(PRG)?
+        .args(["--samefile", &bad])
+        .assert()
+        .failure()
+        .stderr(predicate::str::is_match(format!(
+            "{}: .* [(]os error 2[)]",
+            bad
+        ))?);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_user() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--user", "no-such-user-findr"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Invalid --user \"no-such-user-findr\"",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_mtime() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--mtime", "1d"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid --mtime \"1d\""));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_newer() -> Result<()> {
+    let bad = gen_bad_file();
+    Command::cargo_bin(PRG)?
+        .args(["--newer", &bad])
+        .assert()
+        .failure()
+        .stderr(predicate::str::is_match(format!(
+            "{}: .* [(]os error 2[)]",
+            bad
+        ))?);
+    Ok(())
 }
 
 // --------------------------------------------------
@@ -309,3 +1245,295 @@ fn unreadable_dir() -> Result<()> {
     assert!(stderr.contains("cant-touch-this: Permission denied"));
     Ok(())
 }
+
+// --------------------------------------------------
+#[test]
+fn xtype_f() -> Result<()> {
+    // d/b.csv はファイルへのシンボリックリンクなのでファイルとして扱う
+    run(
+        &["tests/inputs/d", "--xtype", "f"],
+        "tests/expected/xtype_f.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn xtype_l() -> Result<()> {
+    // リンク先をたどれるリンクはリンクとして扱わない
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs/d", "--xtype", "l"])
+        .assert()
+        .success()
+        .stdout("");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn xtype_l_broken() -> Result<()> {
+    let dir = tmp_dir("xtype")?;
+    std::os::unix::fs::symlink("nonexistent", dir.join("broken"))?;
+    std::os::unix::fs::symlink("/", dir.join("root"))?;
+    let output = Command::cargo_bin(PRG)?
+        .arg(&dir)
+        .args(["--xtype", "l"])
+        .output();
+    fs::remove_dir_all(&dir)?;
+
+    let output = output?;
+    assert!(output.status.success());
+    let expected = format!("{}\n", dir.join("broken").display());
+    assert_eq!(String::from_utf8(output.stdout)?, expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn or_type_f_d() -> Result<()> {
+    run(
+        &["tests/inputs/a", "-t", "f", "--or", "-t", "d"],
+        "tests/expected/or_type_f_d.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn or_name_csv_mp3() -> Result<()> {
+    // 組の中の条件はすべて満たす必要がある (-t f はシンボリックリンクの b.csv を除く)
+    run(
+        &[
+            "tests/inputs",
+            "-n",
+            "\\.csv$",
+            "-t",
+            "f",
+            "--or",
+            "-n",
+            "\\.mp3$",
+        ],
+        "tests/expected/or_name_csv_mp3.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_path_after_or() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-t", "f", "--or", "tests/inputs"])
+        .assert()
+        .failure()
+        .stderr("Search paths must come before --or\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_option_after_or() -> Result<()> {
+    // 後ろの組のフィルタ以外の指定は使われないのでエラーにする
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-t", "f", "--or", "-t", "d", "--sort"])
+        .assert()
+        .failure()
+        .stderr("--sort must come before --or\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn or_in_exec() -> Result<()> {
+    // --exec の ; までにある --or は区切りではなくコマンドの引数
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs/a/b", "-t", "f", "--sort"])
+        .args(["--exec", "echo", "{}", "--or", ";"])
+        .assert()
+        .success()
+        .stdout("tests/inputs/a/b/b.csv --or\ntests/inputs/a/b/c/c.mp3 --or\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn or_as_value() -> Result<()> {
+    // 値を取るオプションの直後の --or はその値になる
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-n", "--or"])
+        .assert()
+        .success()
+        .stdout("");
+    Ok(())
+}
+
+// --------------------------------------------------
+// .git はリポジトリに含められないので、テストのたびに作る
+fn make_hidden_dir(name: &str) -> Result<PathBuf> {
+    let dir = tmp_dir(name)?;
+    fs::create_dir_all(dir.join(".git/objects"))?;
+    fs::create_dir_all(dir.join("src"))?;
+    fs::File::create(dir.join(".git/HEAD"))?;
+    fs::File::create(dir.join(".git/objects/pack"))?;
+    fs::File::create(dir.join(".env"))?;
+    fs::File::create(dir.join("src/main.rs"))?;
+    fs::File::create(dir.join("README.md"))?;
+    Ok(dir)
+}
+
+// --------------------------------------------------
+#[test]
+fn no_hidden() -> Result<()> {
+    run_tmp(
+        make_hidden_dir("no-hidden")?,
+        &["--no-hidden"],
+        &["README.md", "src/main.rs"],
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn hidden_default() -> Result<()> {
+    let all = [
+        ".env",
+        ".git/HEAD",
+        ".git/objects/pack",
+        "README.md",
+        "src/main.rs",
+    ];
+    run_tmp(make_hidden_dir("hidden-default")?, &[], &all)?;
+    // 後に指定したほうが優先される
+    run_tmp(
+        make_hidden_dir("hidden-last")?,
+        &["--no-hidden", "--hidden"],
+        &all,
+    )
+}
+
+// --------------------------------------------------
+fn make_delete_dir(name: &str) -> Result<PathBuf> {
+    let dir = tmp_dir(name)?;
+    fs::create_dir_all(dir.join("sub"))?;
+    fs::create_dir_all(dir.join("empty"))?;
+    fs::File::create(dir.join("a.txt"))?;
+    fs::File::create(dir.join("b.csv"))?;
+    fs::File::create(dir.join("sub/c.txt"))?;
+    Ok(dir)
+}
+
+// dir の中に残っているパスを名前順に返す
+fn remaining(dir: &Path) -> Result<Vec<String>> {
+    let mut paths = vec![];
+    for entry in walkdir::WalkDir::new(dir).min_depth(1) {
+        let entry = entry?;
+        let path = entry.path().strip_prefix(dir)?;
+        paths.push(path.to_string_lossy().to_string());
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+// --------------------------------------------------
+#[test]
+fn delete_files() -> Result<()> {
+    let dir = make_delete_dir("delete-files")?;
+    let output = Command::cargo_bin(PRG)?
+        .arg(&dir)
+        .args(["-t", "f", "-n", r"\.txt$", "--delete"])
+        .output();
+    let left = remaining(&dir);
+    fs::remove_dir_all(&dir)?;
+
+    let output = output?;
+    assert!(output.status.success());
+    // 消したパスは表示しない
+    assert!(output.stdout.is_empty());
+    assert_eq!(left?, ["b.csv", "empty", "sub"]);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn delete_non_empty_dir() -> Result<()> {
+    // 空でないディレクトリは消せずにエラーになるが、ほかは消し続ける
+    let dir = make_delete_dir("delete-dirs")?;
+    let output = Command::cargo_bin(PRG)?
+        .arg(&dir)
+        .args(["-t", "d", "-n", "^(sub|empty)$", "--delete"])
+        .output();
+    let left = remaining(&dir);
+    fs::remove_dir_all(&dir)?;
+
+    let output = output?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr)?;
+    let expected = format!("findr: cannot delete {}: ", dir.join("sub").display());
+    assert!(stderr.starts_with(&expected));
+    assert_eq!(stderr.lines().count(), 1);
+    assert_eq!(left?, ["a.txt", "b.csv", "sub", "sub/c.txt"]);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn delete_all() -> Result<()> {
+    // 中身を先に消すので、検索パスのディレクトリも消せる
+    let dir = make_delete_dir("delete-all")?;
+    let output = Command::cargo_bin(PRG)?.arg(&dir).arg("--delete").output();
+    let exists = dir.exists();
+    if exists {
+        fs::remove_dir_all(&dir)?;
+    }
+
+    assert!(output?.status.success());
+    assert!(!exists);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_delete_with_exec() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "--delete", "--exec", "echo", "{}", ";"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    Ok(())
+}
+
+// --------------------------------------------------
+// a.txt と b.txt は同じファイルへのハードリンク
+fn make_links_dir(name: &str) -> Result<PathBuf> {
+    let dir = tmp_dir(name)?;
+    fs::File::create(dir.join("a.txt"))?;
+    fs::hard_link(dir.join("a.txt"), dir.join("b.txt"))?;
+    fs::File::create(dir.join("c.txt"))?;
+    Ok(dir)
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn links_greater() -> Result<()> {
+    run_tmp(
+        make_links_dir("links-greater")?,
+        &["--links", "+1"],
+        &["a.txt", "b.txt"],
+    )
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn links_equal_less() -> Result<()> {
+    run_tmp(make_links_dir("links-equal")?, &["--links", "1"], &["c.txt"])?;
+    run_tmp(make_links_dir("links-less")?, &["--links", "-2"], &["c.txt"])
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_links() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--links", "12x"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid --links \"12x\""));
+    Ok(())
+}
@@ -1,9 +1,19 @@
-use std::fs;
-use std::os::unix::fs::MetadataExt;
-use std::{error::Error, path::PathBuf};
+use std::cmp::Ordering;
+use std::fs::{self, Metadata};
+use std::io::{self, IsTerminal};
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
+use ansi_term::Colour;
+use chrono::format::{Item, StrftimeItems};
 use chrono::{DateTime, Local};
 use clap::{App, Arg};
+use glob::Pattern;
+use serde_json::json;
 use tabular::{Row, Table};
 
 // mod owner;
@@ -11,11 +21,113 @@ use tabular::{Row, Table};
 
 type MyResult<T> = Result<T, Box<dyn Error>>;
 
+const DEFAULT_TIME_FORMAT: &str = "%b %d %y %H:%M";
+// -s と total のブロック数の既定の単位
+const DEFAULT_BLOCK_SIZE: u64 = 1024;
+// -h のサイズ表示の最小幅 ("1023K" が収まる)
+const HUMAN_SIZE_WIDTH: usize = 5;
+// -C で端末の幅がわからないときの幅
+const DEFAULT_WIDTH: usize = 80;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+enum QuoteMode {
+    // 空白や特殊文字を含む名前だけをシングルクォートで囲む
+    #[default]
+    Shell,
+    // すべての名前をダブルクォートで囲み、エスケープする
+    C,
+}
+
+// -t での並べ替えと -l で表示する時刻
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+enum TimeField {
+    // 更新日時 (mtime)
+    #[default]
+    Modified,
+    // inode の変更日時 (ctime)
+    Changed,
+    // アクセス日時 (atime)
+    Accessed,
+}
+
+// -l でないときの名前の並べ方
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+enum OutputMode {
+    // 1 行に 1 つ (-1)
+    #[default]
+    OneLine,
+    // 端末の幅に合わせて複数列に並べる (-C)
+    Columns,
+}
+
+// シンボリックリンクについて、リンク自体とリンク先のどちらの情報を使うか
+// リンク先がないときはどれでもリンク自体の情報を使う
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+enum Dereference {
+    // 引数のリンクだけをたどる (-H、または -l も -d もないとき)
+    #[default]
+    CommandLine,
+    // どのリンクもたどらない (-l か -d があるとき)
+    Never,
+    // ディレクトリの中のリンクもたどる (-L)
+    Always,
+}
+
 #[derive(Debug)]
 pub struct Config {
     paths: Vec<String>,
     long: bool,
     show_hidden: bool,
+    inode: bool,
+    color: bool,
+    dir_self: bool,
+    group_dirs: bool,
+    quote: QuoteMode,
+    numeric_ids: bool,
+    json: bool,
+    time_format: String,
+    show_blocks: bool,
+    human: bool,
+    block_size: Option<u64>,
+    natural: bool,
+    time_field: TimeField,
+    sort_time: bool,
+    reverse: bool,
+    output: OutputMode,
+    ignore_backups: bool,
+    ignore: Vec<Pattern>,
+    hide: Vec<Pattern>,
+    dereference: Dereference,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            paths: vec![],
+            long: false,
+            show_hidden: false,
+            inode: false,
+            color: false,
+            dir_self: false,
+            group_dirs: false,
+            quote: QuoteMode::default(),
+            numeric_ids: false,
+            json: false,
+            time_format: DEFAULT_TIME_FORMAT.to_string(),
+            show_blocks: false,
+            human: false,
+            block_size: None,
+            natural: false,
+            time_field: TimeField::default(),
+            sort_time: false,
+            reverse: false,
+            output: OutputMode::default(),
+            ignore_backups: false,
+            ignore: vec![],
+            hide: vec![],
+            dereference: Dereference::default(),
+        }
+    }
 }
 
 pub fn get_args() -> MyResult<Config> {
@@ -37,6 +149,30 @@ pub fn get_args() -> MyResult<Config> {
                 .takes_value(false)
                 .help("Show all files"),
         )
+        .arg(
+            Arg::with_name("ignore_backups")
+                .short("B")
+                .long("ignore-backups")
+                .takes_value(false)
+                .help("Do not list entries ending with ~"),
+        )
+        .arg(
+            Arg::with_name("ignore")
+                .short("I")
+                .long("ignore")
+                .value_name("PATTERN")
+                .help("Do not list entries matching the shell PATTERN")
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("hide")
+                .long("hide")
+                .value_name("PATTERN")
+                .help("Do not list entries matching the shell PATTERN (overridden by -a)")
+                .multiple(true)
+                .number_of_values(1),
+        )
         .arg(
             Arg::with_name("long")
                 .short("l")
@@ -44,45 +180,533 @@ pub fn get_args() -> MyResult<Config> {
                 .takes_value(false)
                 .help("Long listing"),
         )
+        .arg(
+            Arg::with_name("numeric_ids")
+                .short("n")
+                .long("numeric-uid-gid")
+                .takes_value(false)
+                .help("Like -l, but list numeric user and group IDs"),
+        )
+        .arg(
+            Arg::with_name("size")
+                .short("s")
+                .long("size")
+                .takes_value(false)
+                .help("Print the allocated size of each file, in blocks"),
+        )
+        .arg(
+            Arg::with_name("human")
+                .short("h")
+                .long("human-readable")
+                .takes_value(false)
+                .help("With -l, print sizes like 1.0K 234M 2.0G"),
+        )
+        .arg(
+            Arg::with_name("block_size")
+                .long("block-size")
+                .value_name("SIZE")
+                .conflicts_with("human")
+                .help("Scale -l sizes, -s and total by SIZE (e.g. 1K, 1M, 1G)"),
+        )
+        .arg(
+            Arg::with_name("json")
+                .long("json")
+                .takes_value(false)
+                .help("Print entries as a JSON array"),
+        )
+        .arg(
+            Arg::with_name("time_style")
+                .long("time-style")
+                .value_name("STYLE")
+                .help("Time format: full-iso, long-iso, iso or +FORMAT"),
+        )
+        .arg(
+            Arg::with_name("full_time")
+                .long("full-time")
+                .takes_value(false)
+                .conflicts_with("time_style")
+                .help("Like -l --time-style=full-iso"),
+        )
+        .arg(
+            Arg::with_name("inode")
+                .short("i")
+                .long("inode")
+                .takes_value(false)
+                .help("Print the inode number of each file"),
+        )
+        .arg(
+            Arg::with_name("directory")
+                .short("d")
+                .long("directory")
+                .takes_value(false)
+                .help("List directories themselves, not their contents"),
+        )
+        .arg(
+            Arg::with_name("dereference")
+                .short("L")
+                .long("dereference")
+                .takes_value(false)
+                .help("Show information for the targets of all symbolic links"),
+        )
+        .arg(
+            Arg::with_name("dereference_command_line")
+                .short("H")
+                .long("dereference-command-line")
+                .takes_value(false)
+                .help("Follow symbolic links listed on the command line"),
+        )
+        .arg(
+            Arg::with_name("natural")
+                .short("v")
+                .long("natural")
+                .takes_value(false)
+                .help("Natural sort of (version) numbers within names"),
+        )
+        .arg(
+            Arg::with_name("sort_time")
+                .short("t")
+                .takes_value(false)
+                .help("Sort by time, newest first"),
+        )
+        .arg(
+            Arg::with_name("ctime")
+                .short("c")
+                .takes_value(false)
+                .conflicts_with("atime")
+                .help("With -l, show the inode change time (sort by it with -t); otherwise sort by it"),
+        )
+        .arg(
+            Arg::with_name("atime")
+                .short("u")
+                .takes_value(false)
+                .help("With -l, show the access time (sort by it with -t); otherwise sort by it"),
+        )
+        .arg(
+            Arg::with_name("one_line")
+                .short("1")
+                .takes_value(false)
+                .overrides_with("columns")
+                .help("List one entry per line"),
+        )
+        .arg(
+            Arg::with_name("columns")
+                .short("C")
+                .takes_value(false)
+                .overrides_with("one_line")
+                .help("List entries in columns"),
+        )
+        .arg(
+            Arg::with_name("reverse")
+                .short("r")
+                .long("reverse")
+                .takes_value(false)
+                .help("Reverse order while sorting"),
+        )
+        .arg(
+            Arg::with_name("group_dirs")
+                .long("group-directories-first")
+                .takes_value(false)
+                .help("Group directories before files"),
+        )
+        .arg(
+            Arg::with_name("quote_name")
+                .short("Q")
+                .long("quote-name")
+                .takes_value(false)
+                .help("Enclose entry names in double quotes"),
+        )
+        .arg(
+            Arg::with_name("color")
+                .long("color")
+                .value_name("WHEN")
+                .help("Colorize the output")
+                .possible_values(&["auto", "always", "never"])
+                .default_value("never"),
+        )
         .get_matches();
 
+    let color = match matches.value_of("color") {
+        Some("always") => true,
+        Some("auto") => io::stdout().is_terminal(),
+        _ => false,
+    };
+
+    let time_format = if matches.is_present("full_time") {
+        parse_time_style("full-iso")?
+    } else {
+        matches
+            .value_of("time_style")
+            .map(parse_time_style)
+            .transpose()?
+            .unwrap_or_else(|| DEFAULT_TIME_FORMAT.to_string())
+    };
+
+    // --block-size がなければ、ls と同じく LS_BLOCK_SIZE、BLOCK_SIZE の順に環境変数を見る
+    let human = matches.is_present("human");
+    let block_size = match matches.value_of("block_size") {
+        Some(size) => {
+            Some(parse_block_size(size).map_err(|_| format!("invalid --block-size \"{}\"", size))?)
+        }
+        None if human => None,
+        // 環境変数は ls の human-readable など対応していない値のこともあるので、
+        // 解釈できなければ無視する
+        None => ["LS_BLOCK_SIZE", "BLOCK_SIZE"]
+            .iter()
+            .find_map(|name| std::env::var(name).ok())
+            .and_then(|size| parse_block_size(&size).ok()),
+    };
+
+    let time_field = if matches.is_present("ctime") {
+        TimeField::Changed
+    } else if matches.is_present("atime") {
+        TimeField::Accessed
+    } else {
+        TimeField::Modified
+    };
+
+    // -1 も -C もなければ、ls と同じく端末に出力するときだけ複数列にする
+    // パイプに渡すときは 1 行に 1 つなので、スクリプトから扱いやすい
+    let output = if matches.is_present("columns") {
+        OutputMode::Columns
+    } else if matches.is_present("one_line") || !io::stdout().is_terminal() {
+        OutputMode::OneLine
+    } else {
+        OutputMode::Columns
+    };
+
+    let patterns_of = |arg: &str| -> MyResult<Vec<Pattern>> {
+        Ok(matches
+            .values_of_lossy(arg)
+            .unwrap_or_default()
+            .iter()
+            .map(|pat| Pattern::new(pat).map_err(|_| format!("invalid --{} \"{}\"", arg, pat)))
+            .collect::<Result<_, _>>()?)
+    };
+    let ignore = patterns_of("ignore")?;
+    let hide = patterns_of("hide")?;
+
+    let long = matches.is_present("long")
+        || matches.is_present("numeric_ids")
+        || matches.is_present("full_time");
+    // ls と同じく、-l や -d では引数のリンクもリンク自体を表示する
+    let dereference = if matches.is_present("dereference") {
+        Dereference::Always
+    } else if matches.is_present("dereference_command_line") {
+        Dereference::CommandLine
+    } else if long || matches.is_present("directory") {
+        Dereference::Never
+    } else {
+        Dereference::CommandLine
+    };
+
     Ok(Config {
         paths: matches.values_of_lossy("paths").unwrap(),
-        long: matches.is_present("long"),
+        long,
         show_hidden: matches.is_present("all"),
+        ignore_backups: matches.is_present("ignore_backups"),
+        ignore,
+        hide,
+        inode: matches.is_present("inode"),
+        color,
+        dir_self: matches.is_present("directory"),
+        group_dirs: matches.is_present("group_dirs"),
+        reverse: matches.is_present("reverse"),
+        quote: if matches.is_present("quote_name") {
+            QuoteMode::C
+        } else {
+            QuoteMode::Shell
+        },
+        numeric_ids: matches.is_present("numeric_ids"),
+        json: matches.is_present("json"),
+        time_format,
+        show_blocks: matches.is_present("size"),
+        human,
+        block_size,
+        natural: matches.is_present("natural"),
+        time_field,
+        // ls と同じく、-c と -u は -l のときは表示する時刻を変えるだけで、-t がなければ名前順
+        sort_time: matches.is_present("sort_time") || (!long && time_field != TimeField::Modified),
+        output,
+        dereference,
     })
 }
 
-pub fn run(config: Config) -> MyResult<()> {
-    let paths = find_files(&config.paths, config.show_hidden)?;
+// 数値に K, M, G (1024 の累乗) を付けたもの。数値を省略すると 1 とみなす
+fn parse_block_size(size: &str) -> MyResult<u64> {
+    let (digits, unit) = match size.char_indices().last() {
+        Some((i, 'K')) => (&size[..i], 1024),
+        Some((i, 'M')) => (&size[..i], 1024 * 1024),
+        Some((i, 'G')) => (&size[..i], 1024 * 1024 * 1024),
+        _ => (size, 1),
+    };
+    let n = if digits.is_empty() && unit > 1 {
+        1
+    } else if digits.bytes().all(|b| b.is_ascii_digit()) {
+        digits.parse::<u64>()?
+    } else {
+        return Err(From::from(size));
+    };
+    match n.checked_mul(unit) {
+        Some(0) | None => Err(From::from(size)),
+        Some(block_size) => Ok(block_size),
+    }
+}
+
+fn parse_time_style(style: &str) -> MyResult<String> {
+    let format = match style {
+        "full-iso" => "%Y-%m-%d %H:%M:%S%.9f %z",
+        "long-iso" => "%Y-%m-%d %H:%M",
+        "iso" => "%m-%d %H:%M",
+        _ => match style.strip_prefix('+') {
+            Some(format) => format,
+            None => return Err(format!("invalid --time-style \"{}\"", style).into()),
+        },
+    };
+
+    // chrono は不正な書式を表示するときにパニックするので、先に検査しておく
+    if StrftimeItems::new(format).any(|item| item == Item::Error) {
+        return Err(format!("invalid --time-style \"{}\"", style).into());
+    }
+    Ok(format.to_string())
+}
+
+pub fn run(config: Config) -> MyResult<i32> {
+    // ls と同じく、-I は -a でも除き、--hide は -a があれば除かない
+    let mut ignore = config.ignore.clone();
+    if !config.show_hidden {
+        ignore.extend(config.hide.iter().cloned());
+    }
+    if config.json {
+        let (mut paths, errors) = find_files(
+            &config.paths,
+            config.show_hidden,
+            config.ignore_backups,
+            &ignore,
+            config.dir_self,
+            config.dereference,
+        );
+        for e in &errors {
+            eprintln!("lsr: {}", e);
+        }
+        sort_entries(&mut paths, &config);
+        println!("{}", format_json(&paths)?);
+        return Ok(if errors.is_empty() { 0 } else { 1 });
+    }
+
+    // ls と同じく、ディレクトリ以外の引数をまとめて先に表示し、
+    // そのあとディレクトリごとに中身を表示する
+    let (mut dirs, files): (Vec<String>, Vec<String>) =
+        config.paths.iter().cloned().partition(|path| {
+            let follow = config.dereference != Dereference::Never;
+            !config.dir_self && stat(Path::new(path), follow).is_ok_and(|meta| meta.is_dir())
+        });
+    dirs.sort();
+    if config.reverse {
+        dirs.reverse();
+    }
+    let show_headers = config.paths.len() > 1;
+    let mut failed = false;
+    let mut printed = false;
+
+    if !files.is_empty() {
+        let (mut paths, errors) = find_files(
+            &files,
+            config.show_hidden,
+            config.ignore_backups,
+            &ignore,
+            config.dir_self,
+            config.dereference,
+        );
+        for e in &errors {
+            eprintln!("lsr: {}", e);
+        }
+        failed |= !errors.is_empty();
+        if !paths.is_empty() {
+            sort_entries(&mut paths, &config);
+            print_entries(&paths, &config, false)?;
+            printed = true;
+        }
+    }
+
+    for dir in &dirs {
+        let (mut paths, errors) = find_files(
+            std::slice::from_ref(dir),
+            config.show_hidden,
+            config.ignore_backups,
+            &ignore,
+            config.dir_self,
+            config.dereference,
+        );
+        for e in &errors {
+            eprintln!("lsr: {}", e);
+        }
+        failed |= !errors.is_empty();
+        if printed {
+            println!();
+        }
+        if show_headers {
+            println!("{}:", dir);
+        }
+        sort_entries(&mut paths, &config);
+        print_entries(&paths, &config, true)?;
+        printed = true;
+    }
+    Ok(if failed { 1 } else { 0 })
+}
+
+// 名前順に並べてから、指定された並べ方を順に適用する
+fn sort_entries(paths: &mut [(PathBuf, Metadata)], config: &Config) {
+    paths.sort_by(|(a, _), (b, _)| a.cmp(b));
+    if config.natural {
+        paths.sort_by(|(a, _), (b, _)| natural_cmp(&a.to_string_lossy(), &b.to_string_lossy()));
+    }
+    if config.sort_time {
+        // 新しいものから並べる
+        paths.sort_by_key(|(_, meta)| std::cmp::Reverse(entry_time(meta, config.time_field)));
+    }
+    // ls と同じく、--group-directories-first ではディレクトリを先にしたまま
+    // それぞれの中の順序を逆にする
+    if config.reverse {
+        paths.reverse();
+    }
+    if config.group_dirs {
+        // 安定ソートなので、ディレクトリとファイルそれぞれの中の順序は保たれる
+        paths.sort_by_key(|(_, meta)| !meta.is_dir());
+    }
+}
+
+// listing_dir はディレクトリの中身を一覧しているかどうか
+fn print_entries(
+    paths: &[(PathBuf, Metadata)],
+    config: &Config,
+    listing_dir: bool,
+) -> MyResult<()> {
     if config.long {
-        println!("{}", format_output(&paths)?);
+        // ディレクトリの中身を一覧するときは、先頭にブロック数の合計を表示する
+        if listing_dir {
+            println!("total {}", scale_blocks(total_blocks(paths), config));
+        }
+        // 表の最後の行にも改行が付いている
+        print!("{}", format_output(paths, config)?);
     } else {
-        for path in paths {
-            println!("{}", path.display());
+        let mut names = vec![];
+        for (path, meta) in paths {
+            let mut name = String::new();
+            if config.inode {
+                name.push_str(&format!("{} ", meta.ino()));
+            }
+            if config.show_blocks {
+                name.push_str(&format!("{} ", scale_blocks(meta.blocks(), config)));
+            }
+            name.push_str(&display_name(path, meta, config));
+            names.push(name);
+        }
+        match config.output {
+            OutputMode::OneLine => {
+                for name in &names {
+                    println!("{}", name);
+                }
+            }
+            OutputMode::Columns => print!("{}", format_columns(&names, terminal_width())),
         }
     }
     Ok(())
 }
 
-fn find_files(paths: &[String], show_hidden: bool) -> MyResult<Vec<PathBuf>> {
+// ls と同じく環境変数 COLUMNS を端末の幅として使う
+fn terminal_width() -> usize {
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|width| width.parse().ok())
+        .filter(|&width| width > 0)
+        .unwrap_or(DEFAULT_WIDTH)
+}
+
+// ls -C と同じく、上から下へ埋める列に並べる
+// width に収まる範囲でできるだけ行数を少なくし、列の間は 2 文字空ける
+fn format_columns(names: &[String], width: usize) -> String {
+    if names.is_empty() {
+        return String::new();
+    }
+    let widths: Vec<usize> = names.iter().map(|name| visible_width(name)).collect();
+    let layout = |rows: usize| -> Vec<usize> {
+        widths
+            .chunks(rows)
+            .map(|col| col.iter().copied().max().unwrap_or(0))
+            .collect()
+    };
+    // 1 列でも収まらないときは 1 行に 1 つ
+    let rows = (1..names.len())
+        .find(|&rows| {
+            let cols = layout(rows);
+            cols.iter().sum::<usize>() + 2 * (cols.len() - 1) <= width
+        })
+        .unwrap_or(names.len());
+    let col_widths = layout(rows);
+
+    let mut out = String::new();
+    for row in 0..rows {
+        let cells: Vec<usize> = (row..names.len()).step_by(rows).collect();
+        for (col, &i) in cells.iter().enumerate() {
+            out.push_str(&names[i]);
+            if col + 1 < cells.len() {
+                out.push_str(&" ".repeat(col_widths[col] - widths[i] + 2));
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+// 色付けのエスケープシーケンスを除いた表示上の文字数
+fn visible_width(s: &str) -> usize {
+    let mut width = 0;
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // ESC [ ... m を読み飛ばす
+            for c in chars.by_ref() {
+                if c == 'm' {
+                    break;
+                }
+            }
+        } else {
+            width += 1;
+        }
+    }
+    width
+}
+
+// 見つかったエントリと、読めなかったパスのエラーメッセージを返す
+fn find_files(
+    paths: &[String],
+    show_hidden: bool,
+    ignore_backups: bool,
+    ignore: &[Pattern],
+    dir_self: bool,
+    dereference: Dereference,
+) -> (Vec<(PathBuf, Metadata)>, Vec<String>) {
     let mut res = vec![];
+    let mut errors = vec![];
 
     for path in paths {
-        match fs::metadata(path) {
-            Err(e) => eprintln!("{}: {}", path, e),
+        match stat(Path::new(path), dereference != Dereference::Never) {
+            Err(e) => errors.push(format!("{}: {}", path, e)),
             Ok(metadata) => {
-                if metadata.is_file() {
-                    res.push(PathBuf::from(path));
-                } else if metadata.is_dir() {
-                    for entry in fs::read_dir(path)? {
-                        let entry = entry?;
-                        let path = entry.path();
-                        let is_hidden = path.file_name().map_or(false, |file_name| {
-                            file_name.to_string_lossy().starts_with(".")
-                        });
-                        if !is_hidden || show_hidden {
-                            res.push(entry.path());
+                // デバイスファイルなどもそのまま一覧に加える
+                if !metadata.is_dir() || dir_self {
+                    res.push((PathBuf::from(path), metadata));
+                } else {
+                    match list_dir(Path::new(path), show_hidden, ignore_backups, ignore) {
+                        Err(e) => errors.push(format!("{}: {}", path, e)),
+                        Ok(entries) => {
+                            for entry in entries {
+                                // -L でなければシンボリックリンクはリンク自体の情報を使う
+                                match stat(&entry, dereference == Dereference::Always) {
+                                    Err(e) => errors.push(format!("{}: {}", entry.display(), e)),
+                                    Ok(meta) => res.push((entry, meta)),
+                                }
+                            }
                         }
                     }
                 }
@@ -90,47 +714,371 @@ fn find_files(paths: &[String], show_hidden: bool) -> MyResult<Vec<PathBuf>> {
         }
     }
 
+    (res, errors)
+}
+
+// follow ならリンク先の情報を返すが、リンク先が存在しないシンボリックリンクは
+// リンク自体の情報を返す
+fn stat(path: &Path, follow: bool) -> io::Result<Metadata> {
+    if !follow {
+        return fs::symlink_metadata(path);
+    }
+    fs::metadata(path).or_else(|e| match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_symlink() => Ok(metadata),
+        _ => Err(e),
+    })
+}
+
+// ディレクトリ直下のエントリを返す
+// 引数で指定したファイルは除かないので、ここでだけ隠しファイルとバックアップ、
+// ignore のどれかにマッチする名前を除く
+fn list_dir(
+    path: &Path,
+    show_hidden: bool,
+    ignore_backups: bool,
+    ignore: &[Pattern],
+) -> MyResult<Vec<PathBuf>> {
+    let mut res = vec![];
+    for entry in fs::read_dir(path)? {
+        let path = entry?.path();
+        let name = path
+            .file_name()
+            .map(|file_name| file_name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        // ~ で終わるのはエディタのバックアップファイル
+        let skip = (name.starts_with('.') && !show_hidden)
+            || (name.ends_with('~') && ignore_backups)
+            || ignore.iter().any(|pattern| pattern.matches(&name));
+        if !skip {
+            res.push(path);
+        }
+    }
     Ok(res)
 }
 
-fn format_output(paths: &[PathBuf]) -> MyResult<String> {
+// 名前に含まれる数字の並びを数値として比べる (file2 < file10)
+// 数値として等しければ、先頭の 0 が少ないほうを先にする
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a = a.chars().peekable();
+    let mut b = b.chars().peekable();
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let take_digits = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+                    let mut digits = String::new();
+                    while let Some(c) = chars.next_if(|c| c.is_ascii_digit()) {
+                        digits.push(c);
+                    }
+                    digits
+                };
+                let x = take_digits(&mut a);
+                let y = take_digits(&mut b);
+                let (x_trimmed, y_trimmed) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+                let ord = x_trimmed
+                    .len()
+                    .cmp(&y_trimmed.len())
+                    .then_with(|| x_trimmed.cmp(y_trimmed))
+                    .then_with(|| x.len().cmp(&y.len()));
+                if ord != Ordering::Equal {
+                    return ord;
+                }
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(&y);
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+// ctime には SystemTime を返すメソッドがないので、秒とナノ秒から組み立てる
+fn entry_time(meta: &Metadata, field: TimeField) -> SystemTime {
+    let (secs, nsecs) = match field {
+        TimeField::Modified => (meta.mtime(), meta.mtime_nsec()),
+        TimeField::Changed => (meta.ctime(), meta.ctime_nsec()),
+        TimeField::Accessed => (meta.atime(), meta.atime_nsec()),
+    };
+    let nanos = Duration::from_nanos(nsecs as u64);
+    if secs >= 0 {
+        UNIX_EPOCH + Duration::from_secs(secs as u64) + nanos
+    } else {
+        UNIX_EPOCH - Duration::from_secs(secs.unsigned_abs()) + nanos
+    }
+}
+
+// 512 バイト単位のブロック数の合計
+fn total_blocks(paths: &[(PathBuf, Metadata)]) -> u64 {
+    paths.iter().map(|(_, meta)| meta.blocks()).sum()
+}
+
+// 512 バイト単位のブロック数を、ls と同じく --block-size (既定は 1K) の単位に切り上げる
+fn scale_blocks(blocks: u64, config: &Config) -> u64 {
+    scale_size(
+        blocks * 512,
+        config.block_size.unwrap_or(DEFAULT_BLOCK_SIZE),
+    )
+}
+
+fn format_output(paths: &[(PathBuf, Metadata)], config: &Config) -> MyResult<String> {
     //               1   2    3    4    5    6    7    8
     let fmt = "{:<}{:<} {:>} {:<} {:<} {:>} {:<} {:<}";
-    let mut table = Table::new(fmt);
-
-    for path in paths {
-        let meta = path.metadata()?;
+    // -s の場合はブロック数、-i の場合は inode 番号の列を先頭に追加する
+    let fmt = if config.show_blocks {
+        format!("{{:>}} {}", fmt)
+    } else {
+        fmt.to_string()
+    };
+    let fmt = if config.inode {
+        format!("{{:>}} {}", fmt)
+    } else {
+        fmt
+    };
+    let mut table = Table::new(&fmt);
 
+    for (path, meta) in paths {
         let uid = meta.uid();
-        let user = users::get_user_by_uid(uid)
-            .map(|u| u.name().to_string_lossy().into_owned())
-            .unwrap_or_else(|| uid.to_string());
-
         let gid = meta.gid();
-        let group = users::get_group_by_gid(gid)
-            .map(|g| g.name().to_string_lossy().into_owned())
-            .unwrap_or_else(|| gid.to_string());
+        let (user, group) = if config.numeric_ids {
+            (uid.to_string(), gid.to_string())
+        } else {
+            let user = users::get_user_by_uid(uid)
+                .map(|u| u.name().to_string_lossy().into_owned())
+                .unwrap_or_else(|| uid.to_string());
+            let group = users::get_group_by_gid(gid)
+                .map(|g| g.name().to_string_lossy().into_owned())
+                .unwrap_or_else(|| gid.to_string());
+            (user, group)
+        };
 
-        let file_type = if path.is_dir() { "d" } else { "-" };
-        let perms = format_mode(meta.mode());
-        let modified: DateTime<Local> = DateTime::from(meta.modified()?);
+        let file_type = if meta.file_type().is_symlink() {
+            "l"
+        } else if meta.is_dir() {
+            "d"
+        } else if meta.file_type().is_char_device() {
+            "c"
+        } else if meta.file_type().is_block_device() {
+            "b"
+        } else if meta.file_type().is_fifo() {
+            "p"
+        } else if meta.file_type().is_socket() {
+            "s"
+        } else {
+            "-"
+        };
+        let perms = format!("{}{}", format_mode(meta.mode()), xattr_marker(path));
+        // 数値の列は {:>} で右寄せされる
+        // -h のときは単位の有無にかかわらず数値部分が揃うよう、最小幅まで空白で埋めておく
+        let size = if config.human {
+            format!("{:>1$}", format_size(meta, config), HUMAN_SIZE_WIDTH)
+        } else {
+            format_size(meta, config)
+        };
+        let time: DateTime<Local> = DateTime::from(entry_time(meta, config.time_field));
 
+        let mut row = Row::new();
+        if config.inode {
+            row.add_cell(meta.ino()); // inode 番号
+        }
+        if config.show_blocks {
+            row.add_cell(scale_blocks(meta.blocks(), config)); // ブロック数
+        }
         table.add_row(
-            Row::new()
-                .with_cell(file_type) // 1 "d"または"-"
+            row.with_cell(file_type) // 1 "d"または"-"
                 .with_cell(perms) // 2 パーミッション
                 .with_cell(meta.nlink()) // 3 リンク数
                 .with_cell(user) // 4 ユーザー名
                 .with_cell(group) // 5 グループ名
-                .with_cell(meta.len()) // 6 サイズ
-                .with_cell(modified.format("%b %d %y %H:%M")) // 7 更新日時
-                .with_cell(path.display()), // 8 パス
+                .with_cell(size) // 6 サイズ
+                .with_cell(time.format(&config.time_format)) // 7 更新日時 (-c, -u では別の時刻)
+                .with_cell(link_name(path, meta, config)), // 8 パス
         );
     }
 
     Ok(format!("{}", table))
 }
 
+// デバイスファイルはサイズの代わりにデバイス番号を表示する
+// --block-size のときはその単位で切り上げた数を表示する
+fn format_size(meta: &Metadata, config: &Config) -> String {
+    let file_type = meta.file_type();
+    if file_type.is_char_device() || file_type.is_block_device() {
+        let (major, minor) = major_minor(meta.rdev());
+        format!("{}, {}", major, minor)
+    } else if config.human {
+        human_size(meta.len())
+    } else if let Some(block_size) = config.block_size {
+        scale_size(meta.len(), block_size).to_string()
+    } else {
+        meta.len().to_string()
+    }
+}
+
+fn scale_size(bytes: u64, block_size: u64) -> u64 {
+    bytes.div_ceil(block_size)
+}
+
+// ls -h と同じく 1024 単位で切り上げる
+// 1024 未満は単位を付けず、10 未満は小数第 1 位まで表示する
+fn human_size(bytes: u64) -> String {
+    if bytes < 1024 {
+        return bytes.to_string();
+    }
+    let bytes = bytes as u128;
+    let mut divisor: u128 = 1024;
+    for unit in ["K", "M", "G", "T", "P"] {
+        let tenths = (bytes * 10).div_ceil(divisor);
+        if tenths < 100 {
+            return format!("{}.{}{}", tenths / 10, tenths % 10, unit);
+        }
+        let whole = bytes.div_ceil(divisor);
+        if whole < 1024 {
+            return format!("{}{}", whole, unit);
+        }
+        divisor *= 1024;
+    }
+    format!("{:.1}E", bytes as f64 / divisor as f64)
+}
+
+// glibc の gnu_dev_major / gnu_dev_minor と同じ方法で分解する
+fn major_minor(rdev: u64) -> (u64, u64) {
+    let major = ((rdev >> 32) & 0xffff_f000) | ((rdev >> 8) & 0x0000_0fff);
+    let minor = ((rdev >> 12) & 0xffff_ff00) | (rdev & 0x0000_00ff);
+    (major, minor)
+}
+
+fn display_name(path: &Path, meta: &Metadata, config: &Config) -> String {
+    let name = quote_name(&path.display().to_string(), config.quote);
+    colorize(&name, meta, config.color)
+}
+
+// シンボリックリンクはリンク先も表示する
+fn link_name(path: &Path, meta: &Metadata, config: &Config) -> String {
+    let name = display_name(path, meta, config);
+    if !meta.file_type().is_symlink() {
+        return name;
+    }
+    match fs::read_link(path) {
+        Ok(target) => format!(
+            "{} -> {}",
+            name,
+            quote_name(&target.display().to_string(), config.quote)
+        ),
+        Err(_) => format!("{} -> ?", name),
+    }
+}
+
+fn quote_name(name: &str, mode: QuoteMode) -> String {
+    match mode {
+        QuoteMode::Shell => {
+            let special = |c: char| {
+                c.is_whitespace() || c.is_control() || "'\"\\$`*?!&;|<>()[]{}".contains(c)
+            };
+            // GNU ls と同じく、~ と # は名前の先頭にあるときだけ特殊文字として扱う
+            if !name.chars().any(special) && !name.starts_with(['~', '#']) {
+                return name.to_string();
+            }
+            let mut quoted = String::from("'");
+            for c in name.chars() {
+                match c {
+                    '\'' => quoted.push_str("'\\''"),
+                    '\n' => quoted.push_str("'$'\\n''"),
+                    '\t' => quoted.push_str("'$'\\t''"),
+                    c => quoted.push(c),
+                }
+            }
+            quoted.push('\'');
+            quoted
+        }
+        QuoteMode::C => {
+            let mut quoted = String::from("\"");
+            for c in name.chars() {
+                match c {
+                    '"' => quoted.push_str("\\\""),
+                    '\\' => quoted.push_str("\\\\"),
+                    '\n' => quoted.push_str("\\n"),
+                    '\t' => quoted.push_str("\\t"),
+                    '\r' => quoted.push_str("\\r"),
+                    c if c.is_control() => quoted.push_str(&format!("\\{:03o}", c as u32)),
+                    c => quoted.push(c),
+                }
+            }
+            quoted.push('"');
+            quoted
+        }
+    }
+}
+
+// ディレクトリは青、実行可能ファイルは緑、シンボリックリンクはシアンで表示する
+fn colorize(name: &str, meta: &Metadata, enabled: bool) -> String {
+    if !enabled {
+        return name.to_string();
+    }
+
+    let colour = if meta.file_type().is_symlink() {
+        Colour::Cyan
+    } else if meta.is_dir() {
+        Colour::Blue
+    } else if meta.mode() & 0o111 != 0 {
+        Colour::Green
+    } else {
+        return name.to_string();
+    };
+    colour.bold().paint(name).to_string()
+}
+
+fn format_json(entries: &[(PathBuf, Metadata)]) -> MyResult<String> {
+    let mut values = vec![];
+    for (path, meta) in entries {
+        let file_type = if meta.file_type().is_symlink() {
+            "symlink"
+        } else if meta.is_dir() {
+            "dir"
+        } else {
+            "file"
+        };
+        let modified: DateTime<Local> = DateTime::from(meta.modified()?);
+
+        values.push(json!({
+            "name": path.display().to_string(),
+            "type": file_type,
+            "size": meta.len(),
+            "mode": meta.mode() & 0o7777,
+            "uid": meta.uid(),
+            "gid": meta.gid(),
+            "mtime": modified.to_rfc3339(),
+        }));
+    }
+
+    Ok(serde_json::to_string(&values)?)
+}
+
+// ACL があれば "+"、その他の拡張属性があれば "@" を返す
+// 拡張属性を扱えない環境では何も表示しない
+fn xattr_marker(path: &Path) -> &'static str {
+    if !xattr::SUPPORTED_PLATFORM {
+        return "";
+    }
+    match xattr::list(path) {
+        Ok(mut attrs) => {
+            let names: Vec<_> = attrs.by_ref().collect();
+            if names.iter().any(|name| name == "system.posix_acl_access") {
+                "+"
+            } else if names.is_empty() {
+                ""
+            } else {
+                "@"
+            }
+        }
+        Err(_) => "",
+    }
+}
+
 fn format_mode(mode: u32) -> String {
     let fmt = |m: usize| -> &str { ["---", "--x", "-w-", "-wx", "r--", "r-x", "rw-", "rwx"][m] };
 
@@ -142,18 +1090,34 @@ fn format_mode(mode: u32) -> String {
 
 #[cfg(test)]
 mod test {
-    use super::{find_files, format_mode, format_output};
-    use std::path::PathBuf;
+    use super::{
+        colorize, entry_time, find_files, format_columns, format_json, format_mode, format_output,
+        human_size, list_dir, major_minor, natural_cmp, parse_block_size, parse_time_style,
+        quote_name, scale_blocks, scale_size, total_blocks, xattr_marker, Config, Dereference,
+        QuoteMode, TimeField,
+    };
+    use glob::Pattern;
+    use std::os::unix::fs::MetadataExt;
+    use std::{
+        fs::Metadata,
+        path::{Path, PathBuf},
+    };
 
     #[test]
     fn test_find_files() {
         // ディレクトリにある隠しエントリ以外のエントリを検索する
-        let res = find_files(&["tests/inputs".to_string()], false);
-        assert!(res.is_ok());
+        let (res, errors) = find_files(
+            &["tests/inputs".to_string()],
+            false,
+            false,
+            &[],
+            false,
+            Dereference::CommandLine,
+        );
+        assert!(errors.is_empty());
         let mut filenames: Vec<_> = res
-            .unwrap()
             .iter()
-            .map(|entry| entry.display().to_string())
+            .map(|(path, _)| path.display().to_string())
             .collect();
         filenames.sort();
         assert_eq!(
@@ -167,28 +1131,37 @@ mod test {
         );
 
         // 存在するファイルは、隠しファイルであっても検索できるようにする
-        let res = find_files(&["tests/inputs/.hidden".to_string()], false);
-        assert!(res.is_ok());
+        let (res, errors) = find_files(
+            &["tests/inputs/.hidden".to_string()],
+            false,
+            false,
+            &[],
+            false,
+            Dereference::CommandLine,
+        );
+        assert!(errors.is_empty());
         let filenames: Vec<_> = res
-            .unwrap()
             .iter()
-            .map(|entry| entry.display().to_string())
+            .map(|(path, _)| path.display().to_string())
             .collect();
         assert_eq!(filenames, ["tests/inputs/.hidden"]);
 
         // 複数のパスを与えてテストする
-        let res = find_files(
+        let (res, errors) = find_files(
             &[
                 "tests/inputs/bustle.txt".to_string(),
                 "tests/inputs/dir".to_string(),
             ],
             false,
+            false,
+            &[],
+            false,
+            Dereference::CommandLine,
         );
-        assert!(res.is_ok());
+        assert!(errors.is_empty());
         let mut filenames: Vec<_> = res
-            .unwrap()
             .iter()
-            .map(|entry| entry.display().to_string())
+            .map(|(path, _)| path.display().to_string())
             .collect();
         filenames.sort();
         assert_eq!(
@@ -200,12 +1173,18 @@ mod test {
     #[test]
     fn test_find_files_hidden() {
         // ディレクトリにあるすべてのエントリを検索する
-        let res = find_files(&["tests/inputs".to_string()], true);
-        assert!(res.is_ok());
+        let (res, errors) = find_files(
+            &["tests/inputs".to_string()],
+            true,
+            false,
+            &[],
+            false,
+            Dereference::CommandLine,
+        );
+        assert!(errors.is_empty());
         let mut filenames: Vec<_> = res
-            .unwrap()
             .iter()
-            .map(|entry| entry.display().to_string())
+            .map(|(path, _)| path.display().to_string())
             .collect();
         filenames.sort();
         assert_eq!(
@@ -220,12 +1199,270 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_list_dir() {
+        let res = list_dir(Path::new("tests/inputs/dir"), false, false, &[]);
+        assert!(res.is_ok());
+        assert_eq!(
+            res.unwrap(),
+            [PathBuf::from("tests/inputs/dir/spiders.txt")]
+        );
+
+        let res = list_dir(Path::new("tests/inputs/dir"), true, false, &[]);
+        assert!(res.is_ok());
+        let mut filenames = res.unwrap();
+        filenames.sort();
+        assert_eq!(
+            filenames,
+            [
+                PathBuf::from("tests/inputs/dir/.gitkeep"),
+                PathBuf::from("tests/inputs/dir/spiders.txt"),
+            ]
+        );
+
+        assert!(list_dir(Path::new("tests/inputs/nonexistent"), false, false, &[]).is_err());
+    }
+
+    #[test]
+    fn test_list_dir_ignore_backups() {
+        let res = list_dir(Path::new("tests/backups"), false, false, &[]);
+        let mut filenames = res.unwrap();
+        filenames.sort();
+        assert_eq!(
+            filenames,
+            [
+                PathBuf::from("tests/backups/foo.txt"),
+                PathBuf::from("tests/backups/foo.txt~"),
+            ]
+        );
+
+        let res = list_dir(Path::new("tests/backups"), false, true, &[]);
+        assert_eq!(res.unwrap(), [PathBuf::from("tests/backups/foo.txt")]);
+    }
+
+    #[test]
+    fn test_list_dir_ignore() {
+        let ignore = [Pattern::new("*~").unwrap()];
+        let res = list_dir(Path::new("tests/backups"), false, false, &ignore);
+        assert_eq!(res.unwrap(), [PathBuf::from("tests/backups/foo.txt")]);
+
+        // 隠しファイルを表示するときも除く
+        let ignore = [Pattern::new("*.txt").unwrap()];
+        let res = list_dir(Path::new("tests/inputs/dir"), true, false, &ignore);
+        assert_eq!(res.unwrap(), [PathBuf::from("tests/inputs/dir/.gitkeep")]);
+    }
+
+    #[test]
+    fn test_find_files_dir_self() {
+        // ディレクトリの中身ではなく、ディレクトリ自体を返す
+        let (res, errors) = find_files(
+            &["tests/inputs".to_string()],
+            false,
+            false,
+            &[],
+            true,
+            Dereference::CommandLine,
+        );
+        assert!(errors.is_empty());
+        let filenames: Vec<_> = res
+            .iter()
+            .map(|(path, _)| path.display().to_string())
+            .collect();
+        assert_eq!(filenames, ["tests/inputs"]);
+    }
+
+    #[test]
+    fn test_find_files_broken_symlink() {
+        // リンク先が存在しないシンボリックリンクも検索できるようにする
+        let (res, errors) = find_files(
+            &["tests/links/dangling".to_string()],
+            false,
+            false,
+            &[],
+            false,
+            Dereference::CommandLine,
+        );
+        assert!(errors.is_empty());
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].0, PathBuf::from("tests/links/dangling"));
+        assert!(res[0].1.file_type().is_symlink());
+
+        let (res, errors) = find_files(
+            &["tests/links".to_string()],
+            false,
+            false,
+            &[],
+            false,
+            Dereference::CommandLine,
+        );
+        assert!(errors.is_empty());
+        assert_eq!(res.len(), 1);
+        assert!(res[0].1.file_type().is_symlink());
+
+        // -L でもリンク先がなければリンク自体を返す
+        let (res, errors) = find_files(
+            &["tests/links".to_string()],
+            false,
+            false,
+            &[],
+            false,
+            Dereference::Always,
+        );
+        assert!(errors.is_empty());
+        assert!(res[0].1.file_type().is_symlink());
+    }
+
+    #[test]
+    fn test_find_files_dereference() {
+        let is_link = |path: &str, dereference: Dereference| {
+            let (res, errors) =
+                find_files(&[path.to_string()], false, false, &[], false, dereference);
+            assert!(errors.is_empty());
+            res.iter()
+                .map(|(_, meta)| meta.file_type().is_symlink())
+                .collect::<Vec<_>>()
+        };
+
+        // 引数のリンクはたどるが、ディレクトリの中のリンクはたどらない
+        assert_eq!(
+            is_link("tests/symlinks/fox.txt", Dereference::CommandLine),
+            [false]
+        );
+        assert_eq!(
+            is_link("tests/symlinks", Dereference::CommandLine),
+            [true, true]
+        );
+        assert_eq!(
+            is_link("tests/symlinks/fox.txt", Dereference::Never),
+            [true]
+        );
+        assert_eq!(
+            is_link("tests/symlinks", Dereference::Always),
+            [false, false]
+        );
+    }
+
+    #[test]
+    fn test_find_files_error() {
+        // 読めないパスがあっても、残りのパスは検索を続ける
+        let (res, errors) = find_files(
+            &[
+                "tests/inputs/nonexistent".to_string(),
+                "tests/inputs/dir".to_string(),
+            ],
+            false,
+            false,
+            &[],
+            false,
+            Dereference::CommandLine,
+        );
+        let filenames: Vec<_> = res
+            .iter()
+            .map(|(path, _)| path.display().to_string())
+            .collect();
+        assert_eq!(filenames, ["tests/inputs/dir/spiders.txt"]);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("tests/inputs/nonexistent: "));
+    }
+
+    #[test]
+    fn test_colorize() {
+        let (_, dir) = entry("tests/inputs/dir");
+        assert_eq!(colorize("dir", &dir, false), "dir");
+        assert_eq!(colorize("dir", &dir, true), "\x1b[1;34mdir\x1b[0m");
+
+        let (_, file) = entry("tests/inputs/bustle.txt");
+        assert_eq!(colorize("bustle.txt", &file, true), "bustle.txt");
+
+        let exe = std::env::current_exe().unwrap().metadata().unwrap();
+        assert_eq!(colorize("exe", &exe, true), "\x1b[1;32mexe\x1b[0m");
+
+        let link = std::env::temp_dir().join(format!("lsr-colorize-{}", std::process::id()));
+        std::os::unix::fs::symlink("tests/inputs/bustle.txt", &link).unwrap();
+        let meta = link.symlink_metadata().unwrap();
+        std::fs::remove_file(&link).unwrap();
+        assert_eq!(colorize("link", &meta, true), "\x1b[1;36mlink\x1b[0m");
+    }
+
+    #[test]
+    fn test_quote_name() {
+        // デフォルトでは、特殊文字を含む名前だけをシングルクォートで囲む
+        assert_eq!(quote_name("fox.txt", QuoteMode::Shell), "fox.txt");
+        assert_eq!(quote_name("my file.txt", QuoteMode::Shell), "'my file.txt'");
+        assert_eq!(quote_name("it's", QuoteMode::Shell), "'it'\\''s'");
+        assert_eq!(quote_name("a\nb", QuoteMode::Shell), "'a'$'\\n''b'");
+        assert_eq!(quote_name("foo.txt~", QuoteMode::Shell), "foo.txt~");
+        assert_eq!(quote_name("a#b", QuoteMode::Shell), "a#b");
+        assert_eq!(quote_name("~x", QuoteMode::Shell), "'~x'");
+        assert_eq!(quote_name("#x", QuoteMode::Shell), "'#x'");
+
+        // -Q では、すべての名前をダブルクォートで囲む
+        assert_eq!(quote_name("fox.txt", QuoteMode::C), "\"fox.txt\"");
+        assert_eq!(quote_name("my file.txt", QuoteMode::C), "\"my file.txt\"");
+        assert_eq!(quote_name("say \"hi\"", QuoteMode::C), "\"say \\\"hi\\\"\"");
+        assert_eq!(quote_name("a\nb", QuoteMode::C), "\"a\\nb\"");
+    }
+
+    #[test]
+    fn test_total_blocks() {
+        let entries = [entry("tests/inputs/bustle.txt"), entry("tests/inputs/dir")];
+        let expected = entries[0].1.blocks() + entries[1].1.blocks();
+        assert_eq!(total_blocks(&entries), expected);
+        assert_eq!(total_blocks(&[]), 0);
+
+        // total は既定で 1K 単位、--block-size があればその単位で切り上げる
+        let config = Config::default();
+        assert_eq!(
+            scale_blocks(expected, &config),
+            (expected * 512).div_ceil(1024)
+        );
+        assert_eq!(scale_blocks(8, &config), 4);
+        assert_eq!(scale_blocks(1, &config), 1);
+        let config = Config {
+            block_size: Some(4096),
+            ..Default::default()
+        };
+        assert_eq!(scale_blocks(8, &config), 1);
+        assert_eq!(scale_blocks(9, &config), 2);
+        let config = Config {
+            block_size: Some(512),
+            ..Default::default()
+        };
+        assert_eq!(scale_blocks(8, &config), 8);
+    }
+
+    #[test]
+    fn test_format_json() {
+        let res = format_json(&[entry("tests/inputs/bustle.txt"), entry("tests/inputs/dir")]);
+        assert!(res.is_ok());
+
+        let value: serde_json::Value = serde_json::from_str(&res.unwrap()).unwrap();
+        let entries = value.as_array().unwrap();
+        assert_eq!(entries.len(), 2);
+
+        let bustle = &entries[0];
+        assert_eq!(bustle["name"], "tests/inputs/bustle.txt");
+        assert_eq!(bustle["type"], "file");
+        assert_eq!(bustle["size"], 193);
+        assert_eq!(bustle["mode"], 0o644);
+        assert!(chrono::DateTime::parse_from_rfc3339(bustle["mtime"].as_str().unwrap()).is_ok());
+
+        assert_eq!(entries[1]["type"], "dir");
+    }
+
     #[test]
     fn test_format_mode() {
         assert_eq!(format_mode(0o755), "rwxr-xr-x");
         assert_eq!(format_mode(0o421), "r---w---x");
     }
 
+    // helper
+    fn entry(path: &str) -> (PathBuf, Metadata) {
+        let path = PathBuf::from(path);
+        let meta = path.metadata().unwrap();
+        (path, meta)
+    }
+
     // helper
     fn long_match(
         line: &str,
@@ -234,9 +1471,9 @@ mod test {
         expected_size: Option<&str>,
     ) {
         let parts: Vec<_> = line.split_whitespace().collect();
-        assert!(parts.len() > 0 && parts.len() <= 10);
+        assert!(!parts.is_empty() && parts.len() <= 10);
 
-        let perms = parts.get(0).unwrap();
+        let perms = parts.first().unwrap();
         assert_eq!(perms, &expected_perms);
 
         if let Some(size) = expected_size {
@@ -251,9 +1488,9 @@ mod test {
     #[test]
     fn test_format_output_one() {
         let bustle_path = "tests/inputs/bustle.txt";
-        let bustle = PathBuf::from(bustle_path);
+        let bustle = entry(bustle_path);
 
-        let res = format_output(&[bustle]);
+        let res = format_output(&[bustle], &Config::default());
         assert!(res.is_ok());
 
         let out = res.unwrap();
@@ -261,15 +1498,15 @@ mod test {
         assert_eq!(lines.len(), 1);
 
         let line1 = lines.first().unwrap();
-        long_match(&line1, bustle_path, "-rw-r--r--", Some("193"));
+        long_match(line1, bustle_path, "-rw-r--r--", Some("193"));
     }
 
     #[test]
     fn test_format_output_two() {
-        let res = format_output(&[
-            PathBuf::from("tests/inputs/dir"),
-            PathBuf::from("tests/inputs/empty.txt"),
-        ]);
+        let res = format_output(
+            &[entry("tests/inputs/dir"), entry("tests/inputs/empty.txt")],
+            &Config::default(),
+        );
         assert!(res.is_ok());
 
         let out = res.unwrap();
@@ -279,13 +1516,351 @@ mod test {
 
         let empty_line = lines.remove(0);
         long_match(
-            &empty_line,
+            empty_line,
             "tests/inputs/empty.txt",
             "-rw-r--r--",
             Some("0"),
         );
 
         let dir_line = lines.remove(0);
-        long_match(&dir_line, "tests/inputs/dir", "drwxr-xr-x", None);
+        long_match(dir_line, "tests/inputs/dir", "drwxr-xr-x", None);
+    }
+
+    #[test]
+    fn test_format_output_inode() {
+        let bustle_path = "tests/inputs/bustle.txt";
+        let bustle = entry(bustle_path);
+        let ino = bustle.1.ino();
+
+        let config = Config {
+            inode: true,
+            ..Default::default()
+        };
+        let res = format_output(&[bustle], &config);
+        assert!(res.is_ok());
+
+        let out = res.unwrap();
+        let parts: Vec<_> = out.split_whitespace().collect();
+        let first = parts.first().unwrap().parse::<u64>();
+        assert_eq!(first, Ok(ino));
+        assert_eq!(parts.get(1).unwrap(), &"-rw-r--r--");
+        assert_eq!(parts.last().unwrap(), &bustle_path);
+    }
+
+    #[test]
+    fn test_format_output_numeric_ids() {
+        let bustle_path = "tests/inputs/bustle.txt";
+        let bustle = entry(bustle_path);
+        let (uid, gid) = (bustle.1.uid(), bustle.1.gid());
+
+        let config = Config {
+            numeric_ids: true,
+            ..Default::default()
+        };
+        let res = format_output(&[bustle], &config);
+        assert!(res.is_ok());
+
+        let out = res.unwrap();
+        let parts: Vec<_> = out.split_whitespace().collect();
+        assert_eq!(parts.get(2).unwrap(), &uid.to_string());
+        assert_eq!(parts.get(3).unwrap(), &gid.to_string());
+    }
+
+    #[test]
+    fn test_format_output_broken_symlink() {
+        let (res, _) = find_files(
+            &["tests/links/dangling".to_string()],
+            false,
+            false,
+            &[],
+            false,
+            Dereference::CommandLine,
+        );
+        let out = format_output(&res, &Config::default());
+        assert!(out.is_ok());
+
+        let out = out.unwrap();
+        assert!(out.starts_with('l'));
+        assert!(out
+            .trim_end()
+            .ends_with("tests/links/dangling -> nonexistent"));
+    }
+
+    #[test]
+    fn test_parse_time_style() {
+        assert_eq!(parse_time_style("long-iso").unwrap(), "%Y-%m-%d %H:%M");
+        assert_eq!(parse_time_style("+%Y/%m/%d").unwrap(), "%Y/%m/%d");
+        assert!(parse_time_style("full-iso").is_ok());
+        assert!(parse_time_style("iso").is_ok());
+        assert!(parse_time_style("foo").is_err());
+        assert!(parse_time_style("+%Q").is_err());
+    }
+
+    #[test]
+    fn test_format_output_long_iso() {
+        let dir = entry("tests/inputs/dir");
+        let expected = chrono::DateTime::<chrono::Local>::from(dir.1.modified().unwrap())
+            .format("%Y-%m-%d %H:%M")
+            .to_string();
+
+        let config = Config {
+            time_format: parse_time_style("long-iso").unwrap(),
+            ..Default::default()
+        };
+        let res = format_output(&[dir], &config);
+        assert!(res.is_ok());
+
+        let out = res.unwrap();
+        let parts: Vec<_> = out.split_whitespace().collect();
+        assert_eq!(parts.len(), 8);
+        assert_eq!(format!("{} {}", parts[5], parts[6]), expected);
+    }
+
+    #[test]
+    fn test_xattr_marker() {
+        assert_eq!(xattr_marker(Path::new("tests/inputs/bustle.txt")), "");
+
+        let path = std::env::temp_dir().join(format!("lsr-xattr-{}", std::process::id()));
+        std::fs::write(&path, "").unwrap();
+        // 拡張属性に対応していないファイルシステムでは検査しない
+        if xattr::set(&path, "user.lsr", b"test").is_ok() {
+            assert_eq!(xattr_marker(&path), "@");
+
+            let out = format_output(&[entry(path.to_str().unwrap())], &Config::default()).unwrap();
+            let perms = out.split_whitespace().next().unwrap();
+            assert!(perms.ends_with('@'));
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_format_output_align() {
+        let res = format_output(
+            &[
+                entry("tests/inputs/empty.txt"),
+                entry("tests/inputs/bustle.txt"),
+            ],
+            &Config::default(),
+        );
+        assert!(res.is_ok());
+
+        // サイズの列 (5 番目) の終わりの位置が揃っていること
+        let size_end = |line: &str| {
+            let mut rest = line;
+            for _ in 0..5 {
+                rest = rest.trim_start();
+                rest = &rest[rest.find(' ').unwrap()..];
+            }
+            line.len() - rest.len()
+        };
+        let out = res.unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(size_end(lines[0]), size_end(lines[1]));
+        assert!(lines[0][..size_end(lines[0])].ends_with("   0"));
+        assert!(lines[1][..size_end(lines[1])].ends_with(" 193"));
+    }
+
+    #[test]
+    fn test_format_output_blocks() {
+        let bustle = entry("tests/inputs/bustle.txt");
+        // ブロック数は 512 バイト単位から 1K 単位に直して表示する
+        let (ino, blocks) = (bustle.1.ino(), (bustle.1.blocks() * 512).div_ceil(1024));
+
+        let config = Config {
+            inode: true,
+            show_blocks: true,
+            ..Default::default()
+        };
+        let res = format_output(&[bustle], &config);
+        assert!(res.is_ok());
+
+        let out = res.unwrap();
+        let parts: Vec<_> = out.split_whitespace().collect();
+        assert_eq!(parts[0].parse::<u64>(), Ok(ino));
+        assert_eq!(parts[1].parse::<u64>(), Ok(blocks));
+        assert_eq!(parts[2], "-rw-r--r--");
+    }
+
+    #[test]
+    fn test_major_minor() {
+        assert_eq!(major_minor(0x0103), (1, 3));
+        assert_eq!(major_minor(0x0805), (8, 5));
+        // 大きなマイナー番号は上位ビットにも分かれて格納される
+        assert_eq!(major_minor(0x0010_3a05), (58, 0x0105));
+        assert_eq!(major_minor(0x0000_1000_0000_0000), (0x1000, 0));
+    }
+
+    #[test]
+    fn test_format_output_device() {
+        let dev_null = Path::new("/dev/null");
+        if !dev_null.exists() {
+            return;
+        }
+        let res = format_output(&[entry("/dev/null")], &Config::default());
+        assert!(res.is_ok());
+
+        let out = res.unwrap();
+        assert!(out.starts_with('c'));
+        assert!(out.contains(" 1, 3 "));
+    }
+
+    #[test]
+    fn test_human_size() {
+        assert_eq!(human_size(0), "0");
+        assert_eq!(human_size(193), "193");
+        assert_eq!(human_size(1023), "1023");
+        assert_eq!(human_size(1024), "1.0K");
+        // 切り上げる
+        assert_eq!(human_size(1025), "1.1K");
+        assert_eq!(human_size(1500), "1.5K");
+        assert_eq!(human_size(10 * 1024 - 1), "10K");
+        assert_eq!(human_size(10 * 1024 + 1), "11K");
+        assert_eq!(human_size(1024 * 1024 - 1), "1.0M");
+        assert_eq!(human_size(1024 * 1024), "1.0M");
+        assert_eq!(human_size(5 * 1024 * 1024 * 1024), "5.0G");
+    }
+
+    #[test]
+    fn test_format_output_human_align() {
+        let path = std::env::temp_dir().join(format!("lsr-human-{}", std::process::id()));
+        std::fs::write(&path, vec![b'x'; 1500]).unwrap();
+        let big = (path.clone(), std::fs::metadata(&path).unwrap());
+        std::fs::remove_file(&path).unwrap();
+
+        let config = Config {
+            human: true,
+            ..Default::default()
+        };
+        let res = format_output(
+            &[
+                big,
+                entry("tests/inputs/empty.txt"),
+                entry("tests/inputs/bustle.txt"),
+            ],
+            &config,
+        );
+        assert!(res.is_ok());
+
+        // サイズの列 (5 番目) の終わりの位置が揃っていること
+        let size_end = |line: &str| {
+            let mut rest = line;
+            for _ in 0..5 {
+                rest = rest.trim_start();
+                rest = &rest[rest.find(' ').unwrap()..];
+            }
+            line.len() - rest.len()
+        };
+        let out = res.unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(size_end(lines[0]), size_end(lines[1]));
+        assert_eq!(size_end(lines[0]), size_end(lines[2]));
+        assert!(lines[0][..size_end(lines[0])].ends_with(" 1.5K"));
+        assert!(lines[1][..size_end(lines[1])].ends_with("     0"));
+        assert!(lines[2][..size_end(lines[2])].ends_with("   193"));
+    }
+
+    #[test]
+    fn test_parse_block_size() {
+        assert_eq!(parse_block_size("1").unwrap(), 1);
+        assert_eq!(parse_block_size("512").unwrap(), 512);
+        assert_eq!(parse_block_size("K").unwrap(), 1024);
+        assert_eq!(parse_block_size("1K").unwrap(), 1024);
+        assert_eq!(parse_block_size("4K").unwrap(), 4096);
+        assert_eq!(parse_block_size("1M").unwrap(), 1024 * 1024);
+        assert_eq!(parse_block_size("2G").unwrap(), 2 * 1024 * 1024 * 1024);
+
+        for bad in [
+            "",
+            "0",
+            "0K",
+            "1X",
+            "-1",
+            "+1K",
+            "1.5K",
+            "99999999999999999999",
+        ] {
+            assert!(parse_block_size(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_scale_size() {
+        assert_eq!(scale_size(0, 1024), 0);
+        assert_eq!(scale_size(193, 1024), 1);
+        assert_eq!(scale_size(1024, 1024), 1);
+        assert_eq!(scale_size(1025, 1024), 2);
+        assert_eq!(scale_size(193, 1), 193);
+    }
+
+    #[test]
+    fn test_natural_cmp() {
+        use std::cmp::Ordering;
+
+        assert_eq!(natural_cmp("file2", "file10"), Ordering::Less);
+        assert_eq!(natural_cmp("file10", "file2"), Ordering::Greater);
+        assert_eq!(natural_cmp("file2", "file2"), Ordering::Equal);
+        assert_eq!(natural_cmp("file", "file1"), Ordering::Less);
+        assert_eq!(natural_cmp("a10b2", "a10b10"), Ordering::Less);
+        assert_eq!(natural_cmp("a2b", "a10a"), Ordering::Less);
+        assert_eq!(natural_cmp("v1.9.0", "v1.10.0"), Ordering::Less);
+        assert_eq!(natural_cmp("abc", "abd"), Ordering::Less);
+        // 数値として等しければ先頭の 0 が少ないほうが先
+        assert_eq!(natural_cmp("file02", "file2"), Ordering::Greater);
+        assert_eq!(natural_cmp("file02", "file3"), Ordering::Less);
+
+        let mut names = ["file10.txt", "file2.txt", "file1.txt", "a.txt"];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(names, ["a.txt", "file1.txt", "file2.txt", "file10.txt"]);
+    }
+
+    #[test]
+    fn test_entry_time() {
+        let (_, meta) = entry("tests/inputs/bustle.txt");
+        assert_eq!(
+            entry_time(&meta, TimeField::Modified),
+            meta.modified().unwrap()
+        );
+        assert_eq!(
+            entry_time(&meta, TimeField::Accessed),
+            meta.accessed().unwrap()
+        );
+        let changed = entry_time(&meta, TimeField::Changed);
+        assert_eq!(
+            changed
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            meta.ctime() as u64
+        );
+    }
+
+    #[test]
+    fn test_format_columns() {
+        let names = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        // 上から下へ埋め、最後の列の後ろには空白を付けない
+        let res = format_columns(&names(&["a", "bbb", "cc", "d", "e"]), 9);
+        assert_eq!(res, "a    d\nbbb  e\ncc\n");
+
+        // 幅ちょうどに収まるならその行数にする
+        let res = format_columns(&names(&["a", "bbb", "cc", "d", "e"]), 10);
+        assert_eq!(res, "a    cc  e\nbbb  d\n");
+
+        // すべて 1 行に収まる
+        let res = format_columns(&names(&["a", "bbb", "cc"]), 80);
+        assert_eq!(res, "a  bbb  cc\n");
+
+        // 幅が足りなければ 1 行に 1 つ
+        let res = format_columns(&names(&["aaaa", "bbbb"]), 3);
+        assert_eq!(res, "aaaa\nbbbb\n");
+
+        assert_eq!(format_columns(&[], 80), "");
+
+        // 色のエスケープシーケンスは幅に含めない
+        let colored = ansi_term::Colour::Blue.bold().paint("dir").to_string();
+        let res = format_columns(&[colored.clone(), "b".to_string()], 80);
+        assert_eq!(res, format!("{}  b\n", colored));
     }
 }
@@ -1,6 +1,9 @@
 fn main() {
-    if let Err(e) = lsr::get_args().and_then(lsr::run) {
-        eprintln!("{}", e);
-        std::process::exit(1);
+    match lsr::get_args().and_then(lsr::run) {
+        Ok(code) => std::process::exit(code),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
     }
 }
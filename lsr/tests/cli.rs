@@ -4,6 +4,7 @@ use predicates::prelude::*;
 use pretty_assertions::assert_eq;
 use rand::{distributions::Alphanumeric, Rng};
 use std::fs;
+use std::os::unix::fs::MetadataExt;
 
 const PRG: &str = "lsr";
 const HIDDEN: &str = "tests/inputs/.hidden";
@@ -35,11 +36,26 @@ fn bad_file() -> Result<()> {
     Command::cargo_bin(PRG)?
         .arg(&bad)
         .assert()
-        .success()
+        .failure()
         .stderr(predicate::str::contains(expected));
     Ok(())
 }
 
+// --------------------------------------------------
+#[test]
+fn bad_file_with_good() -> Result<()> {
+    let bad = gen_bad_file();
+    let expected =
+        format!("lsr: {}: No such file or directory (os error 2)", &bad);
+    Command::cargo_bin(PRG)?
+        .args([&bad, BUSTLE])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(expected))
+        .stdout(format!("{BUSTLE}\n"));
+    Ok(())
+}
+
 // --------------------------------------------------
 #[test]
 fn no_args() -> Result<()> {
@@ -177,8 +193,13 @@ fn dir2_all() -> Result<()> {
 fn dir_long(args: &[&str], expected: &[(&str, &str, &str)]) -> Result<()> {
     let cmd = Command::cargo_bin(PRG)?.args(args).assert().success();
     let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
-    let lines: Vec<&str> =
+    let mut lines: Vec<&str> =
         stdout.split('\n').filter(|s| !s.is_empty()).collect();
+
+    // 先頭行はブロック数の合計
+    let total = lines.remove(0);
+    assert!(total.starts_with("total "));
+    assert!(total["total ".len()..].parse::<u64>().is_ok());
     assert_eq!(lines.len(), expected.len());
 
     let mut check = vec![];
@@ -246,3 +267,700 @@ fn dir2_long_all() -> Result<()> {
         ],
     )
 }
+
+// --------------------------------------------------
+#[test]
+fn bustle_inode() -> Result<()> {
+    let cmd = Command::cargo_bin(PRG)?
+        .args(["-i", BUSTLE])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let parts: Vec<_> = stdout.split_whitespace().collect();
+    assert_eq!(parts.len(), 2);
+    assert!(parts[0].parse::<u64>().is_ok());
+    assert_eq!(parts[1], BUSTLE);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn color_always_dir() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--color=always", "tests/inputs"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "\x1b[1;34mtests/inputs/dir\x1b[0m\n",
+        ));
+    Ok(())
+}
+
+#[test]
+fn color_never() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--color=never", "tests/inputs"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\x1b[").not());
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dir_self() -> Result<()> {
+    let cmd = Command::cargo_bin(PRG)?
+        .args(["-ld", "tests/inputs/dir"])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let lines: Vec<&str> =
+        stdout.split('\n').filter(|s| !s.is_empty()).collect();
+    assert_eq!(lines.len(), 1);
+    let parts: Vec<_> = lines[0].split_whitespace().collect();
+    assert_eq!(parts.first().unwrap(), &"drwxr-xr-x");
+    assert_eq!(parts.last().unwrap(), &"tests/inputs/dir");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn group_directories_first() -> Result<()> {
+    let cmd = Command::cargo_bin(PRG)?
+        .args(["--group-directories-first", "tests/inputs"])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let lines: Vec<&str> =
+        stdout.split('\n').filter(|s| !s.is_empty()).collect();
+    assert_eq!(lines.len(), 4);
+    assert_eq!(lines[0], "tests/inputs/dir");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn quote_name() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-Q", BUSTLE])
+        .assert()
+        .success()
+        .stdout(format!("\"{BUSTLE}\"\n"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn numeric_uid_gid() -> Result<()> {
+    // -n は -l を含意する
+    let cmd = Command::cargo_bin(PRG)?
+        .args(["-n", BUSTLE])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let parts: Vec<_> = stdout.split_whitespace().collect();
+    assert_eq!(parts.first().unwrap(), &"-rw-r--r--");
+    assert!(parts.get(2).unwrap().parse::<u32>().is_ok());
+    assert!(parts.get(3).unwrap().parse::<u32>().is_ok());
+    assert_eq!(parts.last().unwrap(), &BUSTLE);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn total_blocks_file_only() -> Result<()> {
+    // ファイルだけを一覧するときは合計を表示しない
+    Command::cargo_bin(PRG)?
+        .args(["-l", BUSTLE])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("total").not());
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn json() -> Result<()> {
+    let cmd = Command::cargo_bin(PRG)?
+        .args(["--json", "tests/inputs"])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let value: serde_json::Value = serde_json::from_str(&stdout)?;
+    let entries = value.as_array().unwrap();
+    assert_eq!(entries.len(), 4);
+    let bustle = entries.iter().find(|e| e["name"] == BUSTLE).unwrap();
+    assert_eq!(bustle["size"], 193);
+    assert_eq!(bustle["type"], "file");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn broken_symlink_long() -> Result<()> {
+    let cmd = Command::cargo_bin(PRG)?
+        .args(["-l", "tests/links"])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let lines: Vec<&str> =
+        stdout.split('\n').filter(|s| !s.is_empty()).collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("total "));
+    assert!(lines[1].starts_with('l'));
+    assert!(lines[1].ends_with("tests/links/dangling -> nonexistent"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn time_style_long_iso() -> Result<()> {
+    let cmd = Command::cargo_bin(PRG)?
+        .args(["-l", "--time-style=long-iso", BUSTLE])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let parts: Vec<_> = stdout.split_whitespace().collect();
+    assert_eq!(parts.len(), 8);
+    assert!(chrono::NaiveDate::parse_from_str(parts[5], "%Y-%m-%d").is_ok());
+    assert!(chrono::NaiveTime::parse_from_str(parts[6], "%H:%M").is_ok());
+    Ok(())
+}
+
+#[test]
+fn time_style_bad() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-l", "--time-style=foo", BUSTLE])
+        .assert()
+        .failure()
+        .stderr("invalid --time-style \"foo\"\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn bustle_size() -> Result<()> {
+    // ls と同じく 1K 単位で表示する
+    let blocks = (fs::metadata(BUSTLE)?.blocks() * 512).div_ceil(1024);
+    Command::cargo_bin(PRG)?
+        .args(["-s", BUSTLE])
+        .assert()
+        .success()
+        .stdout(format!("{blocks} {BUSTLE}\n"));
+    Command::cargo_bin(PRG)?
+        .args(["-s", "--block-size=512", BUSTLE])
+        .assert()
+        .success()
+        .stdout(format!("{} {BUSTLE}\n", fs::metadata(BUSTLE)?.blocks()));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn bustle_long_human() -> Result<()> {
+    // 1024 バイト未満は単位を付けない
+    let cmd = Command::cargo_bin(PRG)?
+        .args(["-lh", BUSTLE])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let parts: Vec<_> = stdout.split_whitespace().collect();
+    assert_eq!(parts.get(4).unwrap(), &"193");
+    assert!(stdout.contains("   193 "));
+    Ok(())
+}
+
+// --------------------------------------------------
+fn long_size(args: &[&str], envs: &[(&str, &str)]) -> Result<String> {
+    let cmd = Command::cargo_bin(PRG)?
+        .env_remove("LS_BLOCK_SIZE")
+        .env_remove("BLOCK_SIZE")
+        .envs(envs.iter().copied())
+        .args(args)
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    Ok(stdout.split_whitespace().nth(4).unwrap().to_string())
+}
+
+#[test]
+fn bustle_block_size() -> Result<()> {
+    // 193 バイトは 1K 単位で切り上げて 1
+    assert_eq!(long_size(&["-l", "--block-size=1K", BUSTLE], &[])?, "1");
+    assert_eq!(long_size(&["-l", "--block-size=100", BUSTLE], &[])?, "2");
+    Ok(())
+}
+
+#[test]
+fn bustle_block_size_env() -> Result<()> {
+    assert_eq!(long_size(&["-l", BUSTLE], &[("BLOCK_SIZE", "1K")])?, "1");
+    // LS_BLOCK_SIZE が BLOCK_SIZE より優先される
+    assert_eq!(
+        long_size(
+            &["-l", BUSTLE],
+            &[("LS_BLOCK_SIZE", "10"), ("BLOCK_SIZE", "1K")]
+        )?,
+        "20"
+    );
+    // オプションが環境変数より優先される
+    assert_eq!(
+        long_size(&["-l", "--block-size=1", BUSTLE], &[("BLOCK_SIZE", "1K")])?,
+        "193"
+    );
+    Ok(())
+}
+
+#[test]
+fn block_size_bad() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-l", "--block-size=1X", BUSTLE])
+        .assert()
+        .failure()
+        .stderr("invalid --block-size \"1X\"\n");
+    // 解釈できない環境変数は無視する
+    assert_eq!(
+        long_size(&["-l", BUSTLE], &[("BLOCK_SIZE", "human-readable")])?,
+        "193"
+    );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn natural_sort() -> Result<()> {
+    let dir = std::env::temp_dir().join(format!("lsr-natural-{}", std::process::id()));
+    fs::create_dir_all(&dir)?;
+    for name in ["file10.txt", "file2.txt", "file1.txt"] {
+        fs::File::create(dir.join(name))?;
+    }
+    let output = Command::cargo_bin(PRG)?.arg("-v").arg(&dir).output();
+    fs::remove_dir_all(&dir)?;
+
+    let output = output?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout)?;
+    let expected: String = ["file1.txt", "file2.txt", "file10.txt"]
+        .iter()
+        .map(|name| format!("{}\n", dir.join(name).display()))
+        .collect();
+    assert_eq!(stdout, expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+// 更新日時、アクセス日時、変更日時の順序がそれぞれ異なるファイルを作る
+//   mtime: old < new
+//   atime: new < old
+//   ctime: new < old (old のパーミッションを後から変える)
+fn make_time_dir(name: &str) -> Result<std::path::PathBuf> {
+    use std::os::unix::fs::PermissionsExt;
+    use std::time::{Duration, SystemTime};
+
+    let dir = std::env::temp_dir()
+        .join(format!("lsr-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir)?;
+    let day = Duration::from_secs(24 * 60 * 60);
+    let now = SystemTime::now();
+
+    let old = fs::File::create(dir.join("old.txt"))?;
+    old.set_times(
+        fs::FileTimes::new()
+            .set_modified(now - day * 10)
+            .set_accessed(now - day),
+    )?;
+    let new = fs::File::create(dir.join("new.txt"))?;
+    new.set_times(
+        fs::FileTimes::new()
+            .set_modified(now - day)
+            .set_accessed(now - day * 5),
+    )?;
+    drop(old);
+    drop(new);
+
+    std::thread::sleep(Duration::from_millis(20));
+    fs::set_permissions(dir.join("old.txt"), fs::Permissions::from_mode(0o600))?;
+    Ok(dir)
+}
+
+fn run_time_sort(name: &str, flag: &str, expected: &[&str]) -> Result<()> {
+    let dir = make_time_dir(name)?;
+    let output = Command::cargo_bin(PRG)?.arg(flag).arg(&dir).output();
+    fs::remove_dir_all(&dir)?;
+
+    let output = output?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout)?;
+    let expected: String = expected
+        .iter()
+        .map(|name| format!("{}\n", dir.join(name).display()))
+        .collect();
+    assert_eq!(stdout, expected);
+    Ok(())
+}
+
+#[test]
+fn sort_mtime() -> Result<()> {
+    run_time_sort("sort-mtime", "-t", &["new.txt", "old.txt"])
+}
+
+#[test]
+fn sort_atime() -> Result<()> {
+    run_time_sort("sort-atime", "-u", &["old.txt", "new.txt"])
+}
+
+#[test]
+fn sort_ctime() -> Result<()> {
+    run_time_sort("sort-ctime", "-c", &["old.txt", "new.txt"])
+}
+
+#[test]
+fn long_ctime_sort() -> Result<()> {
+    // -l と一緒の -c は時刻を変えるだけで名前順のまま、-t を付ければ並べ替える
+    let dir = make_time_dir("long-ctime")?;
+    let names = |args: &[&str]| -> Result<Vec<String>> {
+        let output = Command::cargo_bin(PRG)?.args(args).arg(&dir).output()?;
+        assert!(output.status.success());
+        Ok(String::from_utf8(output.stdout)?
+            .lines()
+            .filter(|line| !line.starts_with("total"))
+            .filter_map(|line| line.rsplit('/').next().map(str::to_string))
+            .collect())
+    };
+    let by_name = names(&["-lc"]);
+    let by_ctime = names(&["-lct"]);
+    fs::remove_dir_all(&dir)?;
+
+    assert_eq!(by_name?, ["new.txt", "old.txt"]);
+    assert_eq!(by_ctime?, ["old.txt", "new.txt"]);
+    Ok(())
+}
+
+#[test]
+fn long_atime() -> Result<()> {
+    // -l と一緒に使うと、その時刻を表示する
+    let dir = make_time_dir("long-atime")?;
+    let file = dir.join("new.txt");
+    let accessed = fs::metadata(&file)?.accessed()?;
+    let output = Command::cargo_bin(PRG)?
+        .args(["-lu", "--time-style=+%Y-%m-%dT%H:%M:%S"])
+        .arg(&file)
+        .output();
+    fs::remove_dir_all(&dir)?;
+
+    let stdout = String::from_utf8(output?.stdout)?;
+    let expected = chrono::DateTime::<chrono::Local>::from(accessed)
+        .format("%Y-%m-%dT%H:%M:%S")
+        .to_string();
+    assert_eq!(stdout.split_whitespace().nth(5), Some(expected.as_str()));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dev_null_long() -> Result<()> {
+    let cmd = Command::cargo_bin(PRG)?
+        .args(["-l", "/dev/null"])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    assert!(stdout.starts_with('c'));
+    assert!(stdout.contains(" 1, 3 "));
+    assert!(stdout.trim_end().ends_with("/dev/null"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn pipe_one_per_line() -> Result<()> {
+    // 端末でなければ -C を付けない限り 1 行に 1 つ
+    let expected = format!("{BUSTLE}\n{EMPTY}\n{FOX}\n");
+    Command::cargo_bin(PRG)?
+        .env("COLUMNS", "80")
+        .args([BUSTLE, EMPTY, FOX])
+        .assert()
+        .success()
+        .stdout(expected.clone());
+    Command::cargo_bin(PRG)?
+        .env("COLUMNS", "80")
+        .args(["-C", "-1", BUSTLE, EMPTY, FOX])
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn columns() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .env("COLUMNS", "80")
+        .args(["-C", BUSTLE, EMPTY, FOX])
+        .assert()
+        .success()
+        .stdout(format!("{BUSTLE}  {EMPTY}  {FOX}\n"));
+    // 収まらなければ上から下へ埋める
+    Command::cargo_bin(PRG)?
+        .env("COLUMNS", "50")
+        .args(["-1", "-C", BUSTLE, EMPTY, FOX])
+        .assert()
+        .success()
+        .stdout(format!("{BUSTLE}  {FOX}\n{EMPTY}\n"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn ignore_backups() -> Result<()> {
+    // ~ は名前の先頭にないのでクォートされない
+    let backup = "tests/backups/foo.txt~";
+    let cmd = Command::cargo_bin(PRG)?.arg("tests/backups").assert().success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let mut lines: Vec<&str> = stdout.lines().collect();
+    lines.sort();
+    assert_eq!(lines, ["tests/backups/foo.txt", backup]);
+
+    Command::cargo_bin(PRG)?
+        .args(["-B", "tests/backups"])
+        .assert()
+        .success()
+        .stdout("tests/backups/foo.txt\n");
+
+    // 引数で指定したファイルは除かない
+    Command::cargo_bin(PRG)?
+        .args(["--ignore-backups", backup])
+        .assert()
+        .success()
+        .stdout(format!("{backup}\n"));
+    Ok(())
+}
+
+// --------------------------------------------------
+fn sorted_lines(args: &[&str]) -> Result<Vec<String>> {
+    let cmd = Command::cargo_bin(PRG)?.args(args).assert().success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let mut lines: Vec<String> = stdout.lines().map(str::to_string).collect();
+    lines.sort();
+    Ok(lines)
+}
+
+// --------------------------------------------------
+#[test]
+fn ignore_pattern() -> Result<()> {
+    let dir = "tests/inputs/dir";
+    assert_eq!(sorted_lines(&["-I", "*.txt", "tests/inputs"])?, [dir]);
+    // -a を付けても除く
+    assert_eq!(
+        sorted_lines(&["-a", "--ignore", "*.txt", "tests/inputs"])?,
+        [HIDDEN, dir]
+    );
+    // 引数で指定したファイルは除かない
+    assert_eq!(sorted_lines(&["-I", "*.txt", FOX])?, [FOX]);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn hide_pattern() -> Result<()> {
+    let dir = "tests/inputs/dir";
+    assert_eq!(sorted_lines(&["--hide", "*.txt", "tests/inputs"])?, [dir]);
+    // -a があれば --hide は効かない
+    assert_eq!(
+        sorted_lines(&["-a", "--hide", "*.txt", "tests/inputs"])?,
+        [HIDDEN, BUSTLE, dir, EMPTY, FOX]
+    );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_ignore_pattern() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-I", "[", "tests/inputs"])
+        .assert()
+        .failure()
+        .stderr("invalid --ignore \"[\"\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn files_before_dirs() -> Result<()> {
+    // ファイルを名前順に先に表示し、ディレクトリは見出し付きで名前順に表示する
+    let dir = "tests/inputs/dir";
+    let expected = format!(
+        "{BUSTLE}\n{FOX}\n\ntests/backups:\ntests/backups/foo.txt\n\n\
+         {dir}:\n{dir}/spiders.txt\n"
+    );
+    Command::cargo_bin(PRG)?
+        .args([FOX, dir, "-B", "tests/backups", BUSTLE])
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn single_dir_no_header() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .arg("tests/inputs/dir")
+        .assert()
+        .success()
+        .stdout("tests/inputs/dir/spiders.txt\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+// 1 つのエントリを -l で表示し、パーミッション、サイズ、最後の欄を返す
+fn long_fields(args: &[&str]) -> Result<(String, String, String)> {
+    let cmd = Command::cargo_bin(PRG)?.args(args).assert().success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let parts: Vec<_> = stdout.split_whitespace().collect();
+    Ok((
+        parts[0].to_string(),
+        parts[4].to_string(),
+        parts.last().unwrap().to_string(),
+    ))
+}
+
+// --------------------------------------------------
+#[test]
+fn symlink_long() -> Result<()> {
+    // -l では引数のリンクもリンク自体を表示する
+    let link = "tests/symlinks/fox.txt";
+    let (perms, size, last) = long_fields(&["-l", link])?;
+    assert!(perms.starts_with('l'));
+    assert_eq!(size, "17");
+    assert_eq!(last, "../inputs/fox.txt");
+    Ok(())
+}
+
+#[test]
+fn symlink_long_dereference() -> Result<()> {
+    // -L と -H ではリンク先の情報を表示する
+    let link = "tests/symlinks/fox.txt";
+    for flag in ["-L", "-H"] {
+        let (perms, size, last) = long_fields(&["-l", flag, link])?;
+        assert_eq!(perms, "-rw-------");
+        assert_eq!(size, "45");
+        assert_eq!(last, link);
+    }
+    Ok(())
+}
+
+#[test]
+fn symlink_dir_dereference() -> Result<()> {
+    // -H はディレクトリの中のリンクはたどらない
+    let cmd = Command::cargo_bin(PRG)?
+        .args(["-lH", "tests/symlinks"])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let types: Vec<_> = stdout
+        .lines()
+        .skip(1)
+        .filter(|line| !line.is_empty())
+        .map(|line| &line[..1])
+        .collect();
+    assert_eq!(types, ["l", "l"]);
+
+    // -L はすべてのリンクをたどる
+    let cmd = Command::cargo_bin(PRG)?
+        .args(["-lL", "tests/symlinks"])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let types: Vec<_> = stdout
+        .lines()
+        .skip(1)
+        .filter(|line| !line.is_empty())
+        .map(|line| &line[..1])
+        .collect();
+    assert_eq!(types, ["d", "-"]);
+    Ok(())
+}
+
+#[test]
+fn symlink_dir_contents() -> Result<()> {
+    // -l がなければ引数のディレクトリへのリンクは中身を表示する
+    Command::cargo_bin(PRG)?
+        .arg("tests/symlinks/dir")
+        .assert()
+        .success()
+        .stdout("tests/symlinks/dir/spiders.txt\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn full_iso_nanoseconds() -> Result<()> {
+    use std::time::{Duration, SystemTime};
+
+    // 秒未満の部分も含めて更新日時を設定する
+    let modified = SystemTime::UNIX_EPOCH
+        + Duration::from_secs(1_700_000_000)
+        + Duration::from_nanos(123_456_789);
+    let path = std::env::temp_dir()
+        .join(format!("lsr-full-iso-{}", std::process::id()));
+    fs::File::create(&path)?.set_modified(modified)?;
+    let mut outputs = vec![];
+    for args in [["-l", "--time-style=full-iso"], ["--full-time", "-l"]] {
+        outputs.push(Command::cargo_bin(PRG)?.args(args).arg(&path).output());
+    }
+    fs::remove_file(&path)?;
+
+    let expected = chrono::DateTime::<chrono::Local>::from(modified)
+        .format("%Y-%m-%d %H:%M:%S.123456789 %z")
+        .to_string();
+    for output in outputs {
+        let stdout = String::from_utf8(output?.stdout)?;
+        assert!(stdout.contains(&expected), "{}", stdout);
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn file_dirs_long() -> Result<()> {
+    // 所有者や日時は環境によって変わるので、表の行はパスだけ、
+    // total の行は数を除いて比べる。空行の数はそのまま比べる
+    let cmd = Command::cargo_bin(PRG)?
+        .args(["-l", FOX, "tests/inputs/dir", "tests/backups"])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let lines: Vec<&str> = stdout
+        .lines()
+        .map(|line| match line.split_whitespace().collect::<Vec<_>>()[..] {
+            ["total", _] => "total",
+            [_, _, _, _, _, _, _, _, _, path] => path,
+            _ => line,
+        })
+        .collect();
+    let expected = fs::read_to_string("tests/expected/file_dirs_long.out")?;
+    assert_eq!(lines, expected.lines().collect::<Vec<_>>());
+    assert!(stdout.ends_with("spiders.txt\n"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn reverse() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-r", "tests/inputs"])
+        .assert()
+        .success()
+        .stdout(format!("{FOX}\n{EMPTY}\ntests/inputs/dir\n{BUSTLE}\n"));
+    Ok(())
+}
+
+#[test]
+fn reverse_group_directories_first() -> Result<()> {
+    // ディレクトリは先のまま、ファイルの順序だけが逆になる
+    Command::cargo_bin(PRG)?
+        .args(["--reverse", "--group-directories-first", "tests/inputs"])
+        .assert()
+        .success()
+        .stdout(format!("tests/inputs/dir\n{FOX}\n{EMPTY}\n{BUSTLE}\n"));
+    Ok(())
+}
@@ -5,23 +5,71 @@ use std::io::{Read, Seek, SeekFrom};
 use std::{
     error::Error,
     fs::File,
-    io::{BufRead, BufReader},
+    io::{self, BufRead, BufReader, IsTerminal, Write},
+    thread,
+    time::Duration,
 };
 
 type MyResult<T> = Result<T, Box<dyn Error>>;
 
+// -f で追記を確かめる間隔
+const FOLLOW_INTERVAL: Duration = Duration::from_secs(1);
+
 #[derive(Debug, PartialEq)]
 enum TakeValue {
     PlusZero,
     TakeNum(i64),
 }
 
+// -f で何を追いかけるか
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Follow {
+    // 開いたファイルを追いかける (--follow=descriptor)
+    Descriptor,
+    // 名前を追いかけ、ローテーションされたら開き直す (--follow=name)
+    Name,
+}
+
+// -f で監視しているファイル
+struct Followed<'a> {
+    name: &'a str,
+    file: File,
+    // どこまで表示したか
+    offset: u64,
+    // 続けてサイズが変わらなかった回数
+    unchanged: u32,
+}
+
+// 複数のファイルを表示するときのヘッダー
+#[derive(Debug)]
+struct Header {
+    // {name} をファイル名に置き換える
+    format: String,
+    // 太字で表示するか
+    bold: bool,
+}
+
+impl Header {
+    fn render(&self, name: &str) -> String {
+        let header = self.format.replace("{name}", name);
+        if self.bold {
+            format!("\x1b[1m{}\x1b[0m", header)
+        } else {
+            header
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Config {
     files: Vec<String>,
     lines: TakeValue,
     bytes: Option<TakeValue>,
     quiet: bool,
+    follow: Option<Follow>,
+    max_unchanged_stats: u32,
+    header: Header,
+    ensure_newline: bool,
 }
 
 pub fn get_args() -> MyResult<Config> {
@@ -52,12 +100,47 @@ pub fn get_args() -> MyResult<Config> {
                 .help("Number of bytes")
                 .conflicts_with("lines"),
         )
+        .arg(
+            Arg::with_name("follow")
+                .short("f")
+                .long("follow")
+                .value_name("HOW")
+                .help("Output appended data as the files grow")
+                .takes_value(true)
+                .min_values(0)
+                .require_equals(true)
+                .possible_values(&["name", "descriptor"]),
+        )
+        .arg(
+            Arg::with_name("max_unchanged_stats")
+                .long("max-unchanged-stats")
+                .value_name("N")
+                .help("With --follow=name, reopen a file unchanged for N checks")
+                .default_value("5"),
+        )
         .arg(
             Arg::with_name("quiet")
                 .short("q")
                 .long("quiet")
                 .help("Suppress headers"),
         )
+        .arg(
+            Arg::with_name("header_format")
+                .long("header-format")
+                .value_name("FORMAT")
+                .help("Header format, {name} is replaced with the file name")
+                .default_value("==> {name} <=="),
+        )
+        .arg(
+            Arg::with_name("ensure_newline")
+                .long("ensure-newline")
+                .help("Add a newline if the output of a file does not end with one"),
+        )
+        .arg(
+            Arg::with_name("color")
+                .long("color")
+                .help("Show headers in bold when stdout is a terminal"),
+        )
         .get_matches();
 
     let lines = matches
@@ -68,15 +151,39 @@ pub fn get_args() -> MyResult<Config> {
 
     let bytes = matches
         .value_of("bytes")
-        .map(parse_num)
+        .map(parse_bytes)
         .transpose()
         .map_err(|e| format!("illegal byte count -- {}", e))?;
 
+    // 値のない -f は --follow=descriptor
+    let follow = match matches.value_of("follow") {
+        Some("name") => Some(Follow::Name),
+        _ if matches.is_present("follow") => Some(Follow::Descriptor),
+        _ => None,
+    };
+
+    let max_unchanged_stats = matches
+        .value_of("max_unchanged_stats")
+        .map(|val| {
+            val.parse()
+                .map_err(|_| format!("invalid maximum number of unchanged stats -- {}", val))
+        })
+        .transpose()?
+        .unwrap();
+
     Ok(Config {
         files: matches.values_of_lossy("files").unwrap(),
         lines: lines.unwrap(),
         bytes,
         quiet: matches.is_present("quiet"),
+        follow,
+        max_unchanged_stats,
+        header: Header {
+            format: matches.value_of("header_format").unwrap().to_string(),
+            // パイプやファイルにはエスケープシーケンスを書き込まない
+            bold: matches.is_present("color") && io::stdout().is_terminal(),
+        },
+        ensure_newline: matches.is_present("ensure_newline"),
     })
 }
 
@@ -84,7 +191,7 @@ fn parse_num(val: &str) -> MyResult<TakeValue> {
     match val.parse::<i64>() {
         Ok(n) => Ok(val
             .starts_with('+')
-            .then(|| match n {
+            .then_some(match n {
                 0 => PlusZero,
                 _ => TakeNum(n),
             })
@@ -100,33 +207,155 @@ fn parse_num(val: &str) -> MyResult<TakeValue> {
     }
 }
 
+// バイト数では GNU tail と同じく末尾の b を 512 バイトのブロック数として扱う
+// 行数には単位がないので -n 2b はエラーになる
+fn parse_bytes(val: &str) -> MyResult<TakeValue> {
+    match val.strip_suffix('b') {
+        Some(blocks) => match parse_num(blocks) {
+            Ok(TakeNum(n)) => n
+                .checked_mul(512)
+                .map(TakeNum)
+                .ok_or_else(|| From::from(val)),
+            Ok(PlusZero) => Ok(PlusZero),
+            _ => Err(From::from(val)),
+        },
+        None => parse_num(val),
+    }
+}
+
 pub fn run(config: Config) -> MyResult<()> {
     let num_files = config.files.len();
+    let mut followed = vec![];
     for (file_num, filename) in config.files.iter().enumerate() {
         match File::open(filename) {
             Err(err) => eprintln!("{}: {}", filename, err),
             Ok(file) => {
                 if !config.quiet && num_files > 1 {
                     println!(
-                        "{}==> {} <==",
+                        "{}{}",
                         if file_num > 0 { "\n" } else { "" },
-                        filename
+                        config.header.render(filename)
                     );
                 }
 
-                let (total_lines, total_bytes) = count_lines_bytes(&filename)?;
-                let file = BufReader::new(file);
-                if let Some(num_bytes) = &config.bytes {
-                    print_bytes(file, num_bytes, total_bytes)?;
+                let (total_lines, total_bytes) = count_lines_bytes(filename)?;
+                let mut file = BufReader::new(file);
+                let last_byte = if let Some(num_bytes) = &config.bytes {
+                    print_bytes(&mut file, num_bytes, total_bytes)?
                 } else {
-                    print_lines(file, &config.lines, total_lines)?;
+                    print_lines(&mut file, &config.lines, total_lines)?
+                };
+                if config.follow.is_some() {
+                    // 表示のために読んだところ (何も読まなかったときは数えたところ) から追いかける
+                    // 数えたあとに追記された分を二重に表示しないよう、表示してから決める
+                    let offset = file.stream_position()?.max(total_bytes as u64);
+                    followed.push(Followed {
+                        name: filename,
+                        file: file.into_inner(),
+                        offset,
+                        unchanged: 0,
+                    });
+                }
+                // 何も表示しなかったときは改行を足さない
+                if config.ensure_newline && last_byte.is_some_and(|byte| byte != b'\n') {
+                    println!();
+                }
+            }
+        }
+    }
+
+    if let Some(follow) = config.follow {
+        // GNU tail と同じく、追いかけるファイルが 1 つもなければ待たずに終わる
+        if followed.is_empty() {
+            return Err(From::from("tailr: no files remaining"));
+        }
+        // 最後に表示したのは最後のファイルなので、その追記にはヘッダーを付けない
+        let mut last = followed.len().checked_sub(1);
+        let header = (!config.quiet && num_files > 1).then_some(&config.header);
+        let reopen_after = (follow == Follow::Name).then_some(config.max_unchanged_stats);
+        loop {
+            let mut out = io::stdout().lock();
+            follow_once(&mut followed, &mut last, header, reopen_after, &mut out)?;
+            out.flush()?;
+            drop(out);
+            thread::sleep(FOLLOW_INTERVAL);
+        }
+    }
+    Ok(())
+}
+
+// 各ファイルに追記された分を out に書き込み、offset を進める
+// GNU tail と同じく、header があれば前回と違うファイルから表示するときだけ付ける
+//
+// reopen_after があれば (--follow=name)、サイズが続けてその回数変わらなかったファイルの
+// 名前を調べ直し、別のファイル (ローテーション後の新しいファイル) になっていれば開き直して
+// 先頭から表示する
+fn follow_once(
+    files: &mut [Followed],
+    last: &mut Option<usize>,
+    header: Option<&Header>,
+    reopen_after: Option<u32>,
+    out: &mut impl Write,
+) -> MyResult<()> {
+    for (i, followed) in files.iter_mut().enumerate() {
+        let mut len = followed.file.metadata()?.len();
+        if len == followed.offset {
+            followed.unchanged += 1;
+            if reopen_after.is_some_and(|max| followed.unchanged >= max) {
+                followed.unchanged = 0;
+                if is_replaced(followed)? {
+                    eprintln!(
+                        "tailr: {}: file has been replaced; following new file",
+                        followed.name
+                    );
+                    followed.file = File::open(followed.name)?;
+                    followed.offset = 0;
+                    len = followed.file.metadata()?.len();
                 }
             }
         }
+        // 切り詰められたファイルは先頭から読み直す
+        if len < followed.offset {
+            followed.offset = 0;
+        }
+        if len == followed.offset {
+            continue;
+        }
+        followed.unchanged = 0;
+
+        followed.file.seek(SeekFrom::Start(followed.offset))?;
+        let mut buffer = Vec::new();
+        followed.file.read_to_end(&mut buffer)?;
+        followed.offset += buffer.len() as u64;
+        if buffer.is_empty() {
+            continue;
+        }
+
+        if let Some(header) = header.filter(|_| *last != Some(i)) {
+            writeln!(out, "\n{}", header.render(followed.name))?;
+        }
+        *last = Some(i);
+        out.write_all(&buffer)?;
     }
     Ok(())
 }
 
+// 名前が開いているファイルとは別のファイルを指しているか
+// 名前のファイルがない (ローテーションの途中) ときは開いているファイルを追いかけ続ける
+#[cfg(unix)]
+fn is_replaced(followed: &Followed) -> MyResult<bool> {
+    use std::os::unix::fs::MetadataExt;
+
+    let current = followed.file.metadata()?;
+    Ok(std::fs::metadata(followed.name)
+        .is_ok_and(|meta| (meta.dev(), meta.ino()) != (current.dev(), current.ino())))
+}
+
+#[cfg(not(unix))]
+fn is_replaced(_followed: &Followed) -> MyResult<bool> {
+    Ok(false)
+}
+
 fn count_lines_bytes(filename: &str) -> MyResult<(i64, i64)> {
     let mut file = BufReader::new(File::open(filename)?);
     let mut lines = 0;
@@ -144,7 +373,13 @@ fn count_lines_bytes(filename: &str) -> MyResult<(i64, i64)> {
     Ok((lines, bytes))
 }
 
-fn print_lines(mut file: impl BufRead, num_lines: &TakeValue, total_lines: i64) -> MyResult<()> {
+// print_lines と print_bytes は最後に表示したバイトを返す
+fn print_lines(
+    mut file: impl BufRead,
+    num_lines: &TakeValue,
+    total_lines: i64,
+) -> MyResult<Option<u8>> {
+    let mut last_byte = None;
     if let Some(start_index) = get_start_index(num_lines, total_lines) {
         let mut lines = 0;
         let mut line = String::new();
@@ -155,19 +390,21 @@ fn print_lines(mut file: impl BufRead, num_lines: &TakeValue, total_lines: i64)
             }
             if lines >= start_index {
                 print!("{}", line);
+                last_byte = line.bytes().last();
             }
             lines += 1;
             line.clear();
         }
     }
-    Ok(())
+    Ok(last_byte)
 }
 
 fn print_bytes<T: Read + Seek>(
     mut file: T,
     num_bytes: &TakeValue,
     total_bytes: i64,
-) -> MyResult<()> {
+) -> MyResult<Option<u8>> {
+    let mut last_byte = None;
     if let Some(start_index) = get_start_index(num_bytes, total_bytes) {
         file.seek(SeekFrom::Start(start_index))?;
         let mut buffer = Vec::new();
@@ -175,8 +412,9 @@ fn print_bytes<T: Read + Seek>(
         if !buffer.is_empty() {
             print!("{}", String::from_utf8_lossy(&buffer));
         }
+        last_byte = buffer.last().copied();
     }
-    Ok(())
+    Ok(last_byte)
 }
 
 fn get_start_index(take_val: &TakeValue, total: i64) -> Option<u64> {
@@ -211,7 +449,11 @@ fn get_start_index(take_val: &TakeValue, total: i64) -> Option<u64> {
 
 #[cfg(test)]
 mod tests {
-    use super::{count_lines_bytes, get_start_index, parse_num, TakeValue::*};
+    use super::{
+        count_lines_bytes, follow_once, get_start_index, parse_bytes, parse_num, Followed, Header,
+        TakeValue::*,
+    };
+    use std::fs::File;
 
     #[test]
     fn test_parse_num() {
@@ -268,6 +510,30 @@ mod tests {
         assert_eq!(res.unwrap_err().to_string(), "foo");
     }
 
+    #[test]
+    fn test_parse_bytes() {
+        // 単位がなければ parse_num と同じ
+        assert_eq!(parse_bytes("3").unwrap(), TakeNum(-3));
+        assert_eq!(parse_bytes("+0").unwrap(), PlusZero);
+
+        // b は 512 バイトのブロック
+        assert_eq!(parse_bytes("2b").unwrap(), TakeNum(-1024));
+        assert_eq!(parse_bytes("+2b").unwrap(), TakeNum(1024));
+        assert_eq!(parse_bytes("-1b").unwrap(), TakeNum(-512));
+        assert_eq!(parse_bytes("+0b").unwrap(), PlusZero);
+
+        // 数値のない b やオーバーフローは無効
+        let res = parse_bytes("b");
+        assert!(res.is_err());
+        assert_eq!(res.unwrap_err().to_string(), "b");
+
+        let res = parse_bytes(&format!("{}b", i64::MAX));
+        assert!(res.is_err());
+
+        // 行数には b を付けられない
+        assert!(parse_num("2b").is_err());
+    }
+
     #[test]
     fn test_count_lines_bytes() {
         let res = count_lines_bytes("tests/inputs/one.txt");
@@ -312,4 +578,110 @@ mod tests {
         // ファイル全体を表示するために0を返す
         assert_eq!(get_start_index(&TakeNum(-20), 10), Some(0));
     }
+
+    #[test]
+    fn test_follow_once() {
+        use std::fs::{self, OpenOptions};
+        use std::io::Write;
+
+        let dir = std::env::temp_dir().join(format!("tailr-follow-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let paths = [dir.join("a.txt"), dir.join("b.txt")];
+        for path in &paths {
+            fs::write(path, "start\n").unwrap();
+        }
+        let names: Vec<String> = paths.iter().map(|p| p.display().to_string()).collect();
+        let mut files: Vec<Followed> = names.iter().map(|name| followed(name, 6)).collect();
+        let append = |i: usize, text: &str| {
+            let mut file = OpenOptions::new().append(true).open(&paths[i]).unwrap();
+            file.write_all(text.as_bytes()).unwrap();
+        };
+
+        // 最初の表示は b で終わっている
+        let mut last = Some(1);
+        let header = Some(&Header {
+            format: "==> {name} <==".to_string(),
+            bold: false,
+        });
+        let mut out = vec![];
+        append(1, "b1\n");
+        follow_once(&mut files, &mut last, header, None, &mut out).unwrap();
+        append(1, "b2\n");
+        follow_once(&mut files, &mut last, header, None, &mut out).unwrap();
+        append(0, "a1\n");
+        follow_once(&mut files, &mut last, header, None, &mut out).unwrap();
+        // 何も追記されなければ何も表示しない
+        follow_once(&mut files, &mut last, header, None, &mut out).unwrap();
+        append(1, "b3\n");
+        follow_once(&mut files, &mut last, header, None, &mut out).unwrap();
+
+        // ヘッダーを付けない場合
+        let mut quiet = vec![];
+        append(0, "a2\n");
+        follow_once(&mut files, &mut last, None, None, &mut quiet).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!(
+                "b1\nb2\n\n==> {} <==\na1\n\n==> {} <==\nb3\n",
+                names[0], names[1]
+            )
+        );
+        assert_eq!(String::from_utf8(quiet).unwrap(), "a2\n");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_follow_once_reopen() {
+        use std::fs;
+
+        let dir = std::env::temp_dir().join(format!("tailr-rotate-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("app.log");
+        fs::write(&path, "start\n").unwrap();
+        let name = path.display().to_string();
+        let mut descriptor = [followed(&name, 6)];
+        let mut by_name = [followed(&name, 6)];
+
+        // ローテーションして新しいファイルを作る
+        fs::rename(&path, dir.join("app.log.1")).unwrap();
+        fs::write(&path, "new\n").unwrap();
+
+        let mut last = Some(0);
+        let mut out = vec![];
+        // 2 回続けてサイズが変わらなければ名前を調べ直す
+        follow_once(&mut by_name, &mut last, None, Some(2), &mut out).unwrap();
+        assert!(out.is_empty());
+        follow_once(&mut by_name, &mut last, None, Some(2), &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "new\n");
+
+        // --follow=descriptor では開き直さない
+        let mut out = vec![];
+        for _ in 0..5 {
+            follow_once(&mut descriptor, &mut last, None, None, &mut out).unwrap();
+        }
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(out.is_empty());
+    }
+
+    fn followed(name: &str, offset: u64) -> Followed<'_> {
+        Followed {
+            name,
+            file: File::open(name).unwrap(),
+            offset,
+            unchanged: 0,
+        }
+    }
+
+    #[test]
+    fn test_header_render() {
+        let mut header = Header {
+            format: "--- {name} ---".to_string(),
+            bold: false,
+        };
+        assert_eq!(header.render("a.txt"), "--- a.txt ---");
+        header.bold = true;
+        assert_eq!(header.render("a.txt"), "\x1b[1m--- a.txt ---\x1b[0m");
+    }
 }
@@ -12,6 +12,7 @@ const ONE: &str = "tests/inputs/one.txt";
 const TWO: &str = "tests/inputs/two.txt";
 const THREE: &str = "tests/inputs/three.txt";
 const TWELVE: &str = "tests/inputs/twelve.txt";
+const BLOCKS: &str = "tests/inputs/blocks.txt";
 
 // --------------------------------------------------
 fn random_string() -> String {
@@ -828,3 +829,111 @@ fn multiple_files_c_plus_3() -> Result<()> {
         "tests/expected/all.c+3.out",
     )
 }
+
+// --------------------------------------------------
+#[test]
+fn blocks_c2b() -> Result<()> {
+    // b は 512 バイトのブロック
+    run(&["-c", "2b", BLOCKS], "tests/expected/blocks.txt.c2b.out")
+}
+
+#[test]
+fn dies_lines_blocks() -> Result<()> {
+    // 行数には b を付けられない
+    Command::cargo_bin(PRG)?
+        .args(["-n", "2b", BLOCKS])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("illegal line count -- 2b"));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_max_unchanged_stats() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--follow=name", "--max-unchanged-stats", "12x", ONE])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "invalid maximum number of unchanged stats -- 12x",
+        ));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn header_format() -> Result<()> {
+    let expected = format!(
+        "--- {ONE} ---\nÖne line, four wordś.\n\n--- {TWO} ---\nFour words.\n"
+    );
+    Command::cargo_bin(PRG)?
+        .args(["--header-format=--- {name} ---", "-n", "1", ONE, TWO])
+        .assert()
+        .success()
+        .stdout(expected);
+
+    Ok(())
+}
+
+#[test]
+fn color_not_terminal() -> Result<()> {
+    // 端末でなければ --color を付けても太字にしない
+    run(
+        &["--color", "-n", "1", TWELVE, EMPTY, ONE, THREE, TWO],
+        "tests/expected/all.n1.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn ensure_newline() -> Result<()> {
+    let no_newline = "tests/inputs/no_newline.txt";
+    // 指定しなければそのまま表示する
+    Command::cargo_bin(PRG)?
+        .args(["-n", "1", no_newline])
+        .assert()
+        .success()
+        .stdout("bar");
+    Command::cargo_bin(PRG)?
+        .args(["--ensure-newline", "-n", "1", no_newline])
+        .assert()
+        .success()
+        .stdout("bar\n");
+    Command::cargo_bin(PRG)?
+        .args(["--ensure-newline", "-c", "2", no_newline])
+        .assert()
+        .success()
+        .stdout("ar\n");
+
+    Ok(())
+}
+
+#[test]
+fn ensure_newline_ends_with_newline() -> Result<()> {
+    // 改行で終わっていれば改行を足さない
+    run(&["--ensure-newline", ONE], "tests/expected/one.txt.out")?;
+    run(
+        &["--ensure-newline", "-c", "3", ONE],
+        "tests/expected/one.txt.c3.out",
+    )?;
+    // 何も表示しなければ改行も表示しない
+    run(&["--ensure-newline", EMPTY], "tests/expected/empty.txt.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_follow_no_files() -> Result<()> {
+    // 開けるファイルがなければ待ち続けずに終わる
+    let bad = gen_bad_file();
+    Command::cargo_bin(PRG)?
+        .args(["-f", &bad])
+        .timeout(std::time::Duration::from_secs(5))
+        .assert()
+        .failure()
+        .stderr(predicate::str::ends_with("tailr: no files remaining\n"));
+
+    Ok(())
+}
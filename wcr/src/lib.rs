@@ -1,12 +1,39 @@
 use clap::{App, Arg};
+use rayon::prelude::*;
 use std::{
     error::Error,
-    fs::File,
-    io::{self, BufRead, BufReader},
+    fmt,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, Read},
+    path::Path,
 };
+use unicode_segmentation::UnicodeSegmentation;
 
 type MyResult<T> = Result<T, Box<dyn Error>>;
 
+// 各列の最小幅
+const DEFAULT_WIDTH: usize = 8;
+
+// このサイズ以上の通常ファイルはメモリマップして数える
+const MMAP_THRESHOLD: usize = 16 * 1024 * 1024;
+
+// UTF-8 の BOM (U+FEFF)
+const BOM: &[u8] = b"\xEF\xBB\xBF";
+
+// --progress の値の単位 (MiB)
+const PROGRESS_UNIT: usize = 1024 * 1024;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+enum TotalMode {
+    // 複数のファイルを指定したときだけ合計を表示する
+    #[default]
+    Auto,
+    Always,
+    Never,
+    // 各ファイルの行を表示せず、合計だけを表示する
+    Only,
+}
+
 #[derive(Debug)]
 pub struct Config {
     files: Vec<String>,
@@ -14,14 +41,109 @@ pub struct Config {
     words: bool,
     bytes: bool,
     chars: bool,
+    max_line_length: bool,
+    blank_lines: bool,
+    nonblank_lines: bool,
+    zero_terminated: bool,
+    stdin_name: Option<String>,
+    total: TotalMode,
+    graphemes: bool,
+    jobs: usize,
+    thousands: bool,
+    skip_bom: bool,
+    words_unicode: bool,
+    // 標準入力をこのバイト数読むごとに途中経過を表示する
+    progress: Option<usize>,
+}
+
+/// count_with で数え方を変えるためのオプション
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CountOptions {
+    /// 改行ではなく NUL 文字を区切りとしてレコード (行) を数える
+    pub zero_terminated: bool,
+    /// 文字数として char ではなく書記素クラスタ (見た目の 1 文字) を数える
+    pub graphemes: bool,
+    /// 先頭の UTF-8 の BOM を数えない
+    pub skip_bom: bool,
+    /// 単語数として空白区切りではなく Unicode の単語境界で区切った単語を数える
+    pub words_unicode: bool,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Default, PartialEq)]
 pub struct FileInfo {
     num_lines: usize,
     num_words: usize,
     num_bytes: usize,
     num_chars: usize,
+    max_line_length: usize,
+    num_blank_lines: usize,
+    num_nonblank_lines: usize,
+}
+
+impl FileInfo {
+    pub fn num_lines(&self) -> usize {
+        self.num_lines
+    }
+
+    pub fn num_words(&self) -> usize {
+        self.num_words
+    }
+
+    pub fn num_bytes(&self) -> usize {
+        self.num_bytes
+    }
+
+    pub fn num_chars(&self) -> usize {
+        self.num_chars
+    }
+
+    pub fn max_line_length(&self) -> usize {
+        self.max_line_length
+    }
+
+    /// 空の行と空白だけの行の数
+    pub fn num_blank_lines(&self) -> usize {
+        self.num_blank_lines
+    }
+
+    /// 空白以外の文字を含む行の数
+    pub fn num_nonblank_lines(&self) -> usize {
+        self.num_nonblank_lines
+    }
+
+    // 区切り文字を含む 1 レコード (行) 分を数えて加える
+    fn add_record(&mut self, record: &[u8], options: &CountOptions) {
+        self.num_lines += 1;
+        self.num_bytes += record.len();
+        // 不正なバイトは置換文字 1 文字として扱う (妥当な UTF-8 ならコピーは発生しない)
+        let line = String::from_utf8_lossy(record);
+        self.num_words += count_words(&line, options);
+        self.num_chars += count_chars(&line, options);
+        let content = if options.zero_terminated {
+            line.trim_end_matches('\0')
+        } else {
+            line.trim_end_matches(['\n', '\r'])
+        };
+        self.max_line_length = self.max_line_length.max(display_width(content));
+        if content.trim().is_empty() {
+            self.num_blank_lines += 1;
+        } else {
+            self.num_nonblank_lines += 1;
+        }
+    }
+}
+
+/// wc のデフォルトと同じく、行数・単語数・バイト数を 8 桁右寄せで並べる
+impl fmt::Display for FileInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}{}{}",
+            format_field(self.num_lines, true, DEFAULT_WIDTH),
+            format_field(self.num_words, true, DEFAULT_WIDTH),
+            format_field(self.num_bytes, true, DEFAULT_WIDTH),
+        )
+    }
 }
 
 pub fn get_args() -> MyResult<Config> {
@@ -65,58 +187,306 @@ pub fn get_args() -> MyResult<Config> {
                 .help("Show character count")
                 .takes_value(false),
         )
+        .arg(
+            Arg::with_name("max_line_length")
+                .short("L")
+                .long("max-line-length")
+                .help("Show maximum line length")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("blank_lines")
+                .long("blank-lines")
+                .help("Show count of empty or whitespace-only lines")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("nonblank_lines")
+                .long("nonblank-lines")
+                .help("Show count of lines with non-whitespace characters")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("files0_from")
+                .long("files0-from")
+                .value_name("F")
+                .help("Read input file names from F (NUL-terminated)")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("zero_terminated")
+                .short("z")
+                .long("zero-terminated")
+                .help("Line delimiter is NUL, not newline")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("stdin_name")
+                .long("stdin-name")
+                .value_name("NAME")
+                .help("Show NAME as the file name for standard input")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("total")
+                .long("total")
+                .value_name("WHEN")
+                .help("When to print a line with total counts")
+                .possible_values(&["auto", "always", "never", "only"]),
+        )
+        .arg(
+            Arg::with_name("graphemes")
+                .long("graphemes")
+                .requires("chars")
+                .help("Count grapheme clusters for --chars")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("words_unicode")
+                .long("words-unicode")
+                .help("Count words by Unicode word boundaries")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("jobs")
+                .long("jobs")
+                .value_name("N")
+                .help("Count up to N files in parallel")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("skip_bom")
+                .long("skip-bom")
+                .help("Do not count a leading UTF-8 byte order mark")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("progress")
+                .long("progress")
+                .value_name("MB")
+                .help("Print running counts of stdin to stderr every MB megabytes")
+                .takes_value(true)
+                .min_values(0)
+                .require_equals(true),
+        )
+        .arg(
+            Arg::with_name("thousands")
+                .long("thousands")
+                .help("Group digits of counts with commas (e.g. 1,234)")
+                .takes_value(false),
+        )
         .get_matches();
 
     let mut lines = matches.is_present("lines");
     let mut words = matches.is_present("words");
     let mut bytes = matches.is_present("bytes");
     let chars = matches.is_present("chars");
+    let max_line_length = matches.is_present("max_line_length");
+    let blank_lines = matches.is_present("blank_lines");
+    let nonblank_lines = matches.is_present("nonblank_lines");
 
-    if [lines, words, bytes, chars].iter().all(|v| v == &false) {
+    if [
+        lines,
+        words,
+        bytes,
+        chars,
+        max_line_length,
+        blank_lines,
+        nonblank_lines,
+    ]
+    .iter()
+    .all(|v| v == &false)
+    {
         lines = true;
         words = true;
         bytes = true;
     }
 
+    let jobs = matches
+        .value_of("jobs")
+        .map(parse_positive_int)
+        .transpose()
+        .map_err(|e| format!("invalid number of jobs -- {}", e))?;
+
+    // 値のない --progress は 64 MB ごと
+    let progress = matches
+        .is_present("progress")
+        .then(|| matches.value_of("progress").unwrap_or("64"))
+        .map(|val| {
+            parse_positive_int(val)
+                .and_then(|mb| mb.checked_mul(PROGRESS_UNIT).ok_or_else(|| From::from(val)))
+        })
+        .transpose()
+        .map_err(|e| format!("invalid progress interval -- {}", e))?;
+
+    // --files0-from が指定された場合は位置引数の FILE を無視する
+    let files = match matches.value_of("files0_from") {
+        Some(list) => read_files0(list)?,
+        None => matches.values_of_lossy("files").unwrap(),
+    };
+
     Ok(Config {
-        files: matches.values_of_lossy("files").unwrap(),
+        files,
         lines,
         words,
         bytes,
         chars,
+        max_line_length,
+        blank_lines,
+        nonblank_lines,
+        zero_terminated: matches.is_present("zero_terminated"),
+        stdin_name: matches.value_of("stdin_name").map(String::from),
+        total: match matches.value_of("total") {
+            Some("always") => TotalMode::Always,
+            Some("never") => TotalMode::Never,
+            Some("only") => TotalMode::Only,
+            _ => TotalMode::default(),
+        },
+        graphemes: matches.is_present("graphemes"),
+        words_unicode: matches.is_present("words_unicode"),
+        jobs: jobs.unwrap_or(1),
+        thousands: matches.is_present("thousands"),
+        skip_bom: matches.is_present("skip_bom"),
+        progress,
     })
 }
 
-pub fn run(config: Config) -> MyResult<()> {
-    let mut total_lines = 0;
-    let mut total_words = 0;
-    let mut total_bytes = 0;
-    let mut total_chars = 0;
-    for filename in &config.files {
-        match open(filename) {
-            Err(err) => eprintln!("{}: {}", filename, err),
-            Ok(file) => {
-                let fileinfo = count(file)?;
-                print_fileinfo(&config, &fileinfo, filename);
-
-                total_lines += fileinfo.num_lines;
-                total_words += fileinfo.num_words;
-                total_bytes += fileinfo.num_bytes;
-                total_chars += fileinfo.num_chars;
+/// 終了ステータスを返す。
+/// 開けない・読めないファイルがあってもほかのファイルは数え続け、
+/// 1 つでもエラーがあれば 1、なければ 0 を返す。
+pub fn run(config: Config) -> MyResult<i32> {
+    let mut fileinfos = vec![];
+    let mut total = FileInfo::default();
+    let mut exit_code = 0;
+    for (filename, result) in config.files.iter().zip(count_files(&config)?) {
+        let fileinfo = match result {
+            Ok(fileinfo) => fileinfo,
+            Err(err) => {
+                eprintln!("{}", err);
+                exit_code = 1;
+                continue;
             }
+        };
+
+        total.num_lines += fileinfo.num_lines;
+        total.num_words += fileinfo.num_words;
+        total.num_bytes += fileinfo.num_bytes;
+        total.num_chars += fileinfo.num_chars;
+        // 最大行長は合計ではなく全ファイル中の最大値
+        total.max_line_length = total.max_line_length.max(fileinfo.max_line_length);
+        total.num_blank_lines += fileinfo.num_blank_lines;
+        total.num_nonblank_lines += fileinfo.num_nonblank_lines;
+        fileinfos.push((filename, fileinfo));
+    }
+
+    // 列幅を揃えるため、すべて数え終えてから出力する
+    // 合計は各ファイルの値以上なので、合計から列幅を決めればよい
+    let width = column_width(&config, &total);
+    if config.total != TotalMode::Only {
+        for (filename, fileinfo) in &fileinfos {
+            let name = display_name(&config, filename);
+            println!("{}", format_fileinfo(&config, fileinfo, name, width));
+        }
+    }
+    match config.total {
+        TotalMode::Auto if config.files.len() > 1 => {
+            println!("{}", format_fileinfo(&config, &total, Some("total"), width))
+        }
+        TotalMode::Always => {
+            println!("{}", format_fileinfo(&config, &total, Some("total"), width))
         }
+        // GNU wc と同じく、合計だけを表示するときは "total" を付けない
+        TotalMode::Only => println!("{}", format_fileinfo(&config, &total, None, width)),
+        _ => {}
     }
+    Ok(exit_code)
+}
 
-    if config.files.len() > 1 {
-        let fileinfo = FileInfo {
-            num_lines: total_lines,
-            num_words: total_words,
-            num_bytes: total_bytes,
-            num_chars: total_chars,
-        };
-        print_fileinfo(&config, &fileinfo, "total");
+fn parse_positive_int(val: &str) -> MyResult<usize> {
+    match val.parse() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(From::from(val)),
     }
-    Ok(())
+}
+
+// config.files と同じ順で各ファイルを数えた結果を返す
+// --jobs が 2 以上ならファイルを並列に数えるが、標準入力だけはメインスレッドで読む
+fn count_files(config: &Config) -> MyResult<Vec<Result<FileInfo, String>>> {
+    let options = CountOptions {
+        zero_terminated: config.zero_terminated,
+        graphemes: config.graphemes,
+        skip_bom: config.skip_bom,
+        words_unicode: config.words_unicode,
+    };
+    // BOM を除く場合はファイルサイズをそのまま使えない
+    let bytes_only = config.bytes
+        && !(config.lines
+            || config.words
+            || config.chars
+            || config.max_line_length
+            || config.blank_lines
+            || config.nonblank_lines)
+        && !config.skip_bom;
+    let count = |filename: &str| count_file(filename, &options, bytes_only, config.progress);
+
+    if config.jobs <= 1 {
+        return Ok(config
+            .files
+            .iter()
+            .map(|filename| count(filename))
+            .collect());
+    }
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(config.jobs)
+        .build()?;
+    let results: Vec<_> = pool.install(|| {
+        config
+            .files
+            .par_iter()
+            .map(|filename| (filename != "-").then(|| count(filename)))
+            .collect()
+    });
+    Ok(results
+        .into_iter()
+        .zip(&config.files)
+        .map(|(result, filename)| result.unwrap_or_else(|| count(filename)))
+        .collect())
+}
+
+// エラーはスレッド間で受け渡せるようにファイル名付きの文字列にする
+fn count_file(
+    filename: &str,
+    options: &CountOptions,
+    bytes_only: bool,
+    progress: Option<usize>,
+) -> Result<FileInfo, String> {
+    // ディレクトリは開けても読めないので、読む前に分かりやすいエラーにする
+    if filename != "-" && fs::metadata(filename).is_ok_and(|metadata| metadata.is_dir()) {
+        return Err(format!("wcr: {}: Is a directory", filename));
+    }
+    // バイト数だけが必要な通常ファイルは中身を読まずにファイルサイズを使う
+    if let Some(num_bytes) = bytes_only.then(|| file_size(filename)).flatten() {
+        return Ok(FileInfo {
+            num_bytes,
+            ..Default::default()
+        });
+    }
+    let result = match file_size(filename) {
+        // 大きな通常ファイルは行ごとに読み込まず、メモリマップして数える
+        Some(size) if size >= MMAP_THRESHOLD => count_mmap_with(filename, options),
+        _ => open(filename).and_then(|file| match progress {
+            // 途中経過を表示するのは、終わりの分からない標準入力だけ
+            Some(every) if filename == "-" => count_with_progress(file, options, every, |info| {
+                eprintln!(
+                    "wcr: -: {} lines, {} bytes so far",
+                    info.num_lines, info.num_bytes
+                )
+            }),
+            _ => count_with(file, options),
+        }),
+    };
+    result.map_err(|e| format!("{}: {}", filename, e))
 }
 
 fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
@@ -126,67 +496,303 @@ fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
     }
 }
 
-pub fn count(mut file: impl BufRead) -> MyResult<FileInfo> {
-    let mut num_lines = 0;
-    let mut num_words = 0;
-    let mut num_bytes = 0;
-    let mut num_chars = 0;
-    let mut line = String::new();
+fn read_files0(list: &str) -> MyResult<Vec<String>> {
+    let mut buf = Vec::new();
+    open(list)
+        .and_then(|mut file| Ok(file.read_to_end(&mut buf)?))
+        .map_err(|e| format!("{}: {}", list, e))?;
+    parse_files0(&buf).map_err(|e| format!("{}: {}", list, e).into())
+}
+
+// NUL 区切りのファイル名一覧を分割する
+// 末尾の NUL は区切りではなく終端として扱い、空のエントリを作らない
+fn parse_files0(buf: &[u8]) -> MyResult<Vec<String>> {
+    if buf.is_empty() {
+        return Ok(vec![]);
+    }
+    let buf = buf.strip_suffix(b"\0").unwrap_or(buf);
+    buf.split(|&b| b == b'\0')
+        .map(|name| match name {
+            b"" => Err("invalid zero-length file name".into()),
+            _ => Ok(String::from_utf8_lossy(name).into_owned()),
+        })
+        .collect()
+}
+
+// 標準入力や通常ファイル以外 (パイプなど) は None を返し、読み込みにフォールバックさせる
+fn file_size(filename: &str) -> Option<usize> {
+    if filename == "-" {
+        return None;
+    }
+    fs::metadata(filename)
+        .ok()
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len() as usize)
+}
+
+/// 入力を最後まで読み、行数・単語数・バイト数・文字数・最大行長を数える
+///
+/// ```
+/// use std::io::Cursor;
+///
+/// let info = wcr::count(Cursor::new("a b\n")).unwrap();
+/// assert_eq!(info.num_lines(), 1);
+/// assert_eq!(info.num_words(), 2);
+/// assert_eq!(info.num_bytes(), 4);
+/// assert_eq!(info.num_chars(), 4);
+/// assert_eq!(info.max_line_length(), 3);
+/// assert_eq!(info.to_string(), "       1       2       4");
+/// ```
+pub fn count(file: impl BufRead) -> MyResult<FileInfo> {
+    count_with(file, &CountOptions::default())
+}
+
+/// count と同じだが、options で数え方を指定する
+///
+/// ```
+/// use std::io::Cursor;
+/// use wcr::CountOptions;
+///
+/// let options = CountOptions {
+///     zero_terminated: true,
+///     ..Default::default()
+/// };
+/// let info = wcr::count_with(Cursor::new("one\0two\0"), &options).unwrap();
+/// assert_eq!(info.num_lines(), 2);
+/// ```
+pub fn count_with(file: impl BufRead, options: &CountOptions) -> MyResult<FileInfo> {
+    count_with_progress(file, options, usize::MAX, |_| {})
+}
+
+/// count_with と同じだが、every バイト読むごとに途中までの値で progress を呼ぶ
+///
+/// 1 行を読み終えるたびに調べるので、行が長いと every バイトより間隔が空くことがある
+///
+/// ```
+/// use std::io::Cursor;
+/// use wcr::CountOptions;
+///
+/// let mut updates = vec![];
+/// let options = CountOptions::default();
+/// let input = Cursor::new("a\nb\nc\n");
+/// let info = wcr::count_with_progress(input, &options, 4, |info| {
+///     updates.push(info.num_bytes())
+/// })
+/// .unwrap();
+/// assert_eq!(updates, [4]);
+/// assert_eq!(info.num_bytes(), 6);
+/// ```
+pub fn count_with_progress(
+    mut file: impl BufRead,
+    options: &CountOptions,
+    every: usize,
+    mut progress: impl FnMut(&FileInfo),
+) -> MyResult<FileInfo> {
+    let delimiter = if options.zero_terminated {
+        b'\0'
+    } else {
+        b'\n'
+    };
+    let mut fileinfo = FileInfo::default();
+    let mut buf = Vec::new();
+    let mut first = true;
+    let mut next_progress = every;
     loop {
-        let bytes = file.read_line(&mut line)?;
+        // UTF-8 として不正なバイト列でも行数・バイト数は数えられるようにバイト単位で読む
+        let bytes = file.read_until(delimiter, &mut buf)?;
         if bytes == 0 {
             break;
         }
-        num_lines += 1;
-        num_words += line.split_whitespace().count();
-        num_bytes += bytes;
-        num_chars += line.chars().count();
-        line.clear();
-    }
-
-    Ok(FileInfo {
-        num_lines,
-        num_words,
-        num_bytes,
-        num_chars,
+        let mut record = buf.as_slice();
+        if first && options.skip_bom {
+            record = record.strip_prefix(BOM).unwrap_or(record);
+        }
+        // BOM だけのファイルは空のファイルと同じ
+        if !record.is_empty() {
+            fileinfo.add_record(record, options);
+        }
+        first = false;
+        buf.clear();
+        if fileinfo.num_bytes >= next_progress {
+            progress(&fileinfo);
+            next_progress = (fileinfo.num_bytes / every + 1).saturating_mul(every);
+        }
+    }
+    Ok(fileinfo)
+}
+
+/// ファイルをメモリマップして、count と同じ値を数える
+pub fn count_mmap(path: impl AsRef<Path>) -> MyResult<FileInfo> {
+    count_mmap_with(path, &CountOptions::default())
+}
+
+/// count_mmap と同じだが、options で数え方を指定する
+pub fn count_mmap_with(path: impl AsRef<Path>, options: &CountOptions) -> MyResult<FileInfo> {
+    let file = File::open(path)?;
+    // 空のファイルはマップできない環境があるので読まずに返す
+    if file.metadata()?.len() == 0 {
+        return Ok(FileInfo::default());
+    }
+    // SAFETY: 読み取り専用でマップしている
+    // 数えている途中でほかのプロセスにファイルを切り詰められると SIGBUS になり得るが、
+    // wc と同じく読み込み中の変更は想定しない
+    let mmap = unsafe { memmap2::Mmap::map(&file)? };
+    let delimiter = if options.zero_terminated {
+        b'\0'
+    } else {
+        b'\n'
+    };
+
+    let data: &[u8] = if options.skip_bom {
+        mmap.strip_prefix(BOM).unwrap_or(&mmap)
+    } else {
+        &mmap
+    };
+
+    let mut fileinfo = FileInfo::default();
+    let mut start = 0;
+    for end in memchr::memchr_iter(delimiter, data) {
+        fileinfo.add_record(&data[start..=end], options);
+        start = end + 1;
+    }
+    // 末尾が区切り文字で終わっていない最後の行
+    if start < data.len() {
+        fileinfo.add_record(&data[start..], options);
+    }
+    Ok(fileinfo)
+}
+
+// 既定では wc と同じく空白以外の文字の並びを 1 単語とする
+// --words-unicode では UAX #29 の単語境界で区切り、句読点や空白だけの部分は数えない
+fn count_words(line: &str, options: &CountOptions) -> usize {
+    if options.words_unicode {
+        line.unicode_words().count()
+    } else {
+        line.split_whitespace().count()
+    }
+}
+
+fn count_chars(line: &str, options: &CountOptions) -> usize {
+    if options.graphemes {
+        // CRLF は 1 つの書記素クラスタになる
+        return line.graphemes(true).count();
+    }
+    // CRLF の \r は文字として数えない
+    match options.zero_terminated {
+        false if line.ends_with("\r\n") => line.chars().count() - 1,
+        _ => line.chars().count(),
+    }
+}
+
+// GNU wc -L と同じく、タブを次の 8 の倍数の桁まで進めたときの行の表示幅
+fn display_width(line: &str) -> usize {
+    line.chars().fold(0, |width, c| match c {
+        '\t' => (width / 8 + 1) * 8,
+        _ => width + 1,
     })
 }
 
-fn print_fileinfo(config: &Config, fileinfo: &FileInfo, filename: &str) {
-    println!(
-        "{}{}{}{}{}",
-        if config.lines {
-            format!("{:>8}", fileinfo.num_lines)
-        } else {
-            "".to_string()
-        },
-        if config.words {
-            format!("{:>8}", fileinfo.num_words)
-        } else {
-            "".to_string()
-        },
-        if config.bytes {
-            format!("{:>8}", fileinfo.num_bytes)
-        } else {
-            "".to_string()
-        },
-        if config.chars {
-            format!("{:>8}", fileinfo.num_chars)
-        } else {
-            "".to_string()
-        },
-        if filename != "-" {
-            format!(" {}", filename.to_string())
-        } else {
-            "".to_string()
+// 表示する列のうち最大の値が収まる幅 (DEFAULT_WIDTH 未満にはしない)
+// 列同士がくっつかないように、最大の値の前にも 1 文字の空白を入れる
+fn column_width(config: &Config, fileinfo: &FileInfo) -> usize {
+    [
+        (fileinfo.num_lines, config.lines),
+        (fileinfo.num_words, config.words),
+        (fileinfo.num_bytes, config.bytes),
+        (fileinfo.num_chars, config.chars),
+        (fileinfo.max_line_length, config.max_line_length),
+        (fileinfo.num_blank_lines, config.blank_lines),
+        (fileinfo.num_nonblank_lines, config.nonblank_lines),
+    ]
+    .iter()
+    .filter(|(_, show)| *show)
+    .map(|(value, _)| format_count(config, *value).len() + 1)
+    .fold(DEFAULT_WIDTH, usize::max)
+}
+
+// 標準入力は --stdin-name が指定されたときだけ名前を表示する
+fn display_name<'a>(config: &'a Config, filename: &'a str) -> Option<&'a str> {
+    match filename {
+        "-" => config.stdin_name.as_deref(),
+        _ => Some(filename),
+    }
+}
+
+fn format_fileinfo(
+    config: &Config,
+    fileinfo: &FileInfo,
+    name: Option<&str>,
+    width: usize,
+) -> String {
+    format!(
+        "{}{}{}{}{}{}{}{}",
+        format_field(
+            format_count(config, fileinfo.num_lines),
+            config.lines,
+            width
+        ),
+        format_field(
+            format_count(config, fileinfo.num_words),
+            config.words,
+            width
+        ),
+        format_field(
+            format_count(config, fileinfo.num_bytes),
+            config.bytes,
+            width
+        ),
+        format_field(
+            format_count(config, fileinfo.num_chars),
+            config.chars,
+            width
+        ),
+        format_field(
+            format_count(config, fileinfo.max_line_length),
+            config.max_line_length,
+            width
+        ),
+        format_field(
+            format_count(config, fileinfo.num_blank_lines),
+            config.blank_lines,
+            width
+        ),
+        format_field(
+            format_count(config, fileinfo.num_nonblank_lines),
+            config.nonblank_lines,
+            width
+        ),
+        match name {
+            Some(name) => format!(" {}", name),
+            None => "".to_string(),
         }
     )
 }
 
+// --thousands のときは 3 桁ごとにカンマで区切る
+fn format_count(config: &Config, value: usize) -> String {
+    if config.thousands {
+        group_digits(value)
+    } else {
+        value.to_string()
+    }
+}
+
+fn group_digits(n: usize) -> String {
+    let digits = n.to_string();
+    let mut grouped = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(c);
+    }
+    grouped
+}
+
 // 回答例のコード
-fn format_field(value: usize, show: bool) -> String {
+fn format_field(value: impl fmt::Display, show: bool, width: usize) -> String {
     if show {
-        format!("{:>8}", value)
+        format!("{:>1$}", value, width)
     } else {
         "".to_string()
     }
@@ -194,8 +800,36 @@ fn format_field(value: usize, show: bool) -> String {
 
 #[cfg(test)]
 mod tests {
-    use super::{count, FileInfo};
-    use std::io::Cursor;
+    use super::{
+        column_width, count, count_chars, count_files, count_mmap, count_mmap_with, count_with,
+        count_with_progress, count_words, display_name, display_width, file_size, format_fileinfo,
+        group_digits, parse_files0, parse_positive_int, run, Config, CountOptions, FileInfo,
+        TotalMode,
+    };
+    use std::{fs::File, io::BufReader, io::Cursor};
+
+    // wcr をオプションなしで実行したときの設定
+    fn config() -> Config {
+        Config {
+            files: vec!["-".to_string()],
+            lines: true,
+            words: true,
+            bytes: true,
+            chars: false,
+            max_line_length: false,
+            blank_lines: false,
+            nonblank_lines: false,
+            zero_terminated: false,
+            stdin_name: None,
+            total: TotalMode::Auto,
+            graphemes: false,
+            jobs: 1,
+            thousands: false,
+            skip_bom: false,
+            words_unicode: false,
+            progress: None,
+        }
+    }
 
     #[test]
     fn test_count() {
@@ -205,9 +839,411 @@ mod tests {
         let expected = FileInfo {
             num_lines: 1,
             num_words: 10,
-            num_chars: 48,
+            num_chars: 47,
             num_bytes: 48,
+            max_line_length: 46,
+            num_blank_lines: 0,
+            num_nonblank_lines: 1,
         };
         assert_eq!(info.unwrap(), expected);
     }
+
+    #[test]
+    fn test_count_max_line_length() {
+        // 最終行に改行がなくても長さに含める
+        let text = "short\na much longer line\r\n\nmedium line";
+        let info = count(Cursor::new(text)).unwrap();
+        assert_eq!(info.num_lines, 4);
+        assert_eq!(info.max_line_length, 18);
+
+        let info = count(Cursor::new("")).unwrap();
+        assert_eq!(info.max_line_length, 0);
+
+        let info = count(Cursor::new("あいう\nab\n")).unwrap();
+        assert_eq!(info.max_line_length, 3);
+    }
+
+    #[test]
+    fn test_file_size() {
+        for filename in [
+            "tests/inputs/empty.txt",
+            "tests/inputs/fox.txt",
+            "tests/inputs/atlamal.txt",
+        ] {
+            let file = BufReader::new(File::open(filename).unwrap());
+            let info = count(file).unwrap();
+            assert_eq!(file_size(filename), Some(info.num_bytes));
+        }
+
+        assert_eq!(file_size("-"), None);
+        assert_eq!(file_size("tests/inputs"), None);
+        assert_eq!(file_size("tests/inputs/nonexistent"), None);
+    }
+
+    #[test]
+    fn test_count_invalid_utf8() {
+        let bytes: &[u8] = b"ab\xff cd\n\x80\xfe\nend";
+        let info = count(Cursor::new(bytes)).unwrap();
+        assert_eq!(info.num_lines, 3);
+        assert_eq!(info.num_bytes, 13);
+        assert_eq!(info.num_words, 4);
+        assert_eq!(info.num_chars, 13);
+    }
+
+    #[test]
+    fn test_parse_files0() {
+        let res = parse_files0(b"a.txt\0b c.txt\0");
+        assert_eq!(res.unwrap(), vec!["a.txt", "b c.txt"]);
+
+        let res = parse_files0(b"a.txt\0b.txt");
+        assert_eq!(res.unwrap(), vec!["a.txt", "b.txt"]);
+
+        let res = parse_files0(b"");
+        assert!(res.unwrap().is_empty());
+
+        let res = parse_files0(b"a.txt\0\0b.txt\0");
+        assert!(res.is_err());
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "invalid zero-length file name"
+        );
+
+        assert!(parse_files0(b"\0").is_err());
+    }
+
+    #[test]
+    fn test_display() {
+        let info = count(Cursor::new("I don't want the world.\n")).unwrap();
+        assert_eq!(info.to_string(), "       1       5      24");
+        assert_eq!(
+            format!("{} fox.txt", info),
+            "       1       5      24 fox.txt"
+        );
+    }
+
+    #[test]
+    fn test_column_width() {
+        let config = Config {
+            words: false,
+            ..config()
+        };
+        let small = FileInfo {
+            num_lines: 1,
+            num_bytes: 3,
+            ..Default::default()
+        };
+        let large = FileInfo {
+            num_lines: 12_345,
+            num_words: 99_999_999_999,
+            num_bytes: 1_234_567_890,
+            ..Default::default()
+        };
+
+        // 1 ファイルだけで小さい値ならデフォルトの 8 桁
+        assert_eq!(column_width(&config, &small), 8);
+        assert_eq!(
+            format_fileinfo(&config, &small, Some("small.txt"), 8),
+            "       1       3 small.txt"
+        );
+
+        // 表示しない列 (単語数) は幅に影響しない
+        let width = column_width(&config, &large);
+        assert_eq!(width, 11);
+        assert_eq!(
+            format_fileinfo(&config, &large, Some("large.txt"), width),
+            "      12345 1234567890 large.txt"
+        );
+        assert_eq!(
+            format_fileinfo(&config, &small, Some("small.txt"), width),
+            "          1          3 small.txt"
+        );
+
+        // 8 桁の値でも隣の列との間に空白が入る
+        let eight_digits = FileInfo {
+            num_bytes: 27_017_546,
+            ..small
+        };
+        assert_eq!(column_width(&config, &eight_digits), 9);
+    }
+
+    #[test]
+    fn test_count_zero_terminated() {
+        let options = CountOptions {
+            zero_terminated: true,
+            ..Default::default()
+        };
+        let info = count_with(Cursor::new("one\0two\0"), &options).unwrap();
+        assert_eq!(info.num_lines, 2);
+        assert_eq!(info.num_words, 2);
+        assert_eq!(info.num_bytes, 8);
+        assert_eq!(info.max_line_length, 3);
+
+        // レコードの中の改行は区切りにならず、単語の区切りとして扱う
+        let info = count_with(Cursor::new("a b\nc\0d"), &options).unwrap();
+        assert_eq!(info.num_lines, 2);
+        assert_eq!(info.num_words, 4);
+    }
+
+    #[test]
+    fn test_display_name() {
+        let mut config = config();
+        assert_eq!(display_name(&config, "-"), None);
+        assert_eq!(display_name(&config, "fox.txt"), Some("fox.txt"));
+
+        config.stdin_name = Some("input".to_string());
+        assert_eq!(display_name(&config, "-"), Some("input"));
+        assert_eq!(display_name(&config, "fox.txt"), Some("fox.txt"));
+    }
+
+    #[test]
+    fn test_count_words() {
+        let options = CountOptions::default();
+        assert_eq!(count_words("The quick brown fox\n", &options), 4);
+        // 空白で区切られていなければ 1 単語
+        assert_eq!(count_words("日本語のテキスト\n", &options), 1);
+        assert_eq!(count_words("hello, world!\n", &options), 2);
+
+        let options = CountOptions {
+            words_unicode: true,
+            ..Default::default()
+        };
+        assert_eq!(count_words("The quick brown fox\n", &options), 4);
+        // 漢字は 1 文字ずつ、続くカタカナはまとめて 1 単語
+        assert_eq!(count_words("日本語 テキスト\n", &options), 4);
+        // 句読点だけの部分は単語にならない
+        assert_eq!(count_words("hello , world !\n", &options), 2);
+    }
+
+    #[test]
+    fn test_count_chars() {
+        let options = CountOptions::default();
+        assert_eq!(count_chars("abc\n", &options), 4);
+        assert_eq!(count_chars("abc\r\n", &options), 4);
+        assert_eq!(count_chars("a\rb\n", &options), 4);
+        // 国旗の絵文字は 2 つの regional indicator からなる
+        assert_eq!(count_chars("\u{1f1ef}\u{1f1f5}\n", &options), 3);
+        // e + 結合文字のアキュート・アクセント
+        assert_eq!(count_chars("e\u{301}\n", &options), 3);
+
+        let options = CountOptions {
+            graphemes: true,
+            ..Default::default()
+        };
+        assert_eq!(count_chars("abc\n", &options), 4);
+        assert_eq!(count_chars("abc\r\n", &options), 4);
+        assert_eq!(count_chars("\u{1f1ef}\u{1f1f5}\n", &options), 2);
+        assert_eq!(count_chars("e\u{301}\n", &options), 2);
+    }
+
+    #[test]
+    fn test_parse_positive_int() {
+        assert_eq!(parse_positive_int("4").unwrap(), 4);
+        assert!(parse_positive_int("0").is_err());
+        assert!(parse_positive_int("-1").is_err());
+        assert!(parse_positive_int("foo").is_err());
+    }
+
+    #[test]
+    fn test_count_files_parallel() {
+        let files: Vec<_> = [
+            "tests/inputs/atlamal.txt",
+            "tests/inputs/empty.txt",
+            "tests/inputs/nonexistent",
+            "tests/inputs/fox.txt",
+            "tests/inputs/graphemes.txt",
+            "tests/inputs/atlamal.txt",
+        ]
+        .iter()
+        .map(|f| f.to_string())
+        .collect();
+        let serial = Config {
+            files: files.clone(),
+            max_line_length: true,
+            ..config()
+        };
+        let parallel = Config {
+            files,
+            max_line_length: true,
+            jobs: 4,
+            ..config()
+        };
+
+        let expected = count_files(&serial).unwrap();
+        assert_eq!(expected.len(), 6);
+        assert!(expected[2].is_err());
+        assert_eq!(count_files(&parallel).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_count_mmap() {
+        for filename in [
+            "tests/inputs/empty.txt",
+            "tests/inputs/fox.txt",
+            "tests/inputs/atlamal.txt",
+            "tests/inputs/invalid_utf8.txt",
+            "tests/inputs/graphemes.txt",
+        ] {
+            let file = BufReader::new(File::open(filename).unwrap());
+            assert_eq!(count_mmap(filename).unwrap(), count(file).unwrap());
+        }
+
+        let options = CountOptions {
+            zero_terminated: true,
+            graphemes: true,
+            ..Default::default()
+        };
+        let filename = "tests/inputs/zero.txt";
+        let file = BufReader::new(File::open(filename).unwrap());
+        assert_eq!(
+            count_mmap_with(filename, &options).unwrap(),
+            count_with(file, &options).unwrap()
+        );
+
+        assert!(count_mmap("tests/inputs/nonexistent").is_err());
+    }
+
+    #[test]
+    fn test_run_exit_code() {
+        let good = Config {
+            files: vec!["tests/inputs/fox.txt".to_string()],
+            ..config()
+        };
+        assert_eq!(run(good).unwrap(), 0);
+
+        // 開けないファイルがあっても残りのファイルは数え、終了ステータスは 1
+        let bad = Config {
+            files: vec![
+                "tests/inputs/nonexistent".to_string(),
+                "tests/inputs/fox.txt".to_string(),
+            ],
+            ..config()
+        };
+        assert_eq!(run(bad).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_display_width() {
+        assert_eq!(display_width(""), 0);
+        assert_eq!(display_width("abc"), 3);
+        assert_eq!(display_width("\tx"), 9);
+        assert_eq!(display_width("a\tb"), 9);
+        assert_eq!(display_width("1234567\tx"), 9);
+        assert_eq!(display_width("12345678\tx"), 17);
+        assert_eq!(display_width("\t\t"), 16);
+        assert_eq!(display_width("あ\tい"), 9);
+
+        // 最大行長はタブを展開した幅で数える
+        let info = count(Cursor::new("a\tb\nabcdefgh\n")).unwrap();
+        assert_eq!(info.max_line_length, 9);
+    }
+
+    #[test]
+    fn test_group_digits() {
+        assert_eq!(group_digits(0), "0");
+        assert_eq!(group_digits(7), "7");
+        assert_eq!(group_digits(999), "999");
+        assert_eq!(group_digits(1000), "1,000");
+        assert_eq!(group_digits(12345), "12,345");
+        assert_eq!(group_digits(123456), "123,456");
+        assert_eq!(group_digits(1234567), "1,234,567");
+    }
+
+    #[test]
+    fn test_format_fileinfo_thousands() {
+        let config = Config {
+            thousands: true,
+            ..config()
+        };
+        let info = FileInfo {
+            num_lines: 1234,
+            num_words: 12,
+            num_bytes: 1234567,
+            ..Default::default()
+        };
+        // 列幅はカンマを含めた幅で決める
+        let width = column_width(&config, &info);
+        assert_eq!(width, 10);
+        assert_eq!(
+            format_fileinfo(&config, &info, Some("big.txt"), width),
+            "     1,234        12 1,234,567 big.txt"
+        );
+    }
+
+    #[test]
+    fn test_count_skip_bom() {
+        let text = "\u{feff}hello world\n";
+        let options = CountOptions {
+            skip_bom: true,
+            ..Default::default()
+        };
+
+        let info = count(Cursor::new(text)).unwrap();
+        assert_eq!((info.num_bytes, info.num_chars), (15, 13));
+        let info = count_with(Cursor::new(text), &options).unwrap();
+        assert_eq!((info.num_bytes, info.num_chars), (12, 12));
+        assert_eq!((info.num_lines, info.num_words), (1, 2));
+
+        // 先頭以外の BOM は数える
+        let info = count_with(Cursor::new("a\n\u{feff}b\n"), &options).unwrap();
+        assert_eq!(info.num_bytes, 7);
+
+        // BOM だけなら空のファイルと同じ
+        let info = count_with(Cursor::new("\u{feff}"), &options).unwrap();
+        assert_eq!(info, FileInfo::default());
+    }
+
+    #[test]
+    fn test_count_mmap_skip_bom() {
+        let options = CountOptions {
+            skip_bom: true,
+            ..Default::default()
+        };
+        let info = count_mmap_with("tests/inputs/bom.txt", &options).unwrap();
+        let expected = count_with(
+            BufReader::new(File::open("tests/inputs/bom.txt").unwrap()),
+            &options,
+        )
+        .unwrap();
+        assert_eq!(info, expected);
+        assert_eq!(info.num_bytes, 12);
+    }
+
+    #[test]
+    fn test_count_with_progress() {
+        // 小さなバッファで読み、何度かに分けて入力を受け取る
+        let input = "one\ntwo\nthree\nfour\n".repeat(100);
+        let file = BufReader::with_capacity(16, Cursor::new(input.as_str()));
+        let mut updates = vec![];
+        let info = count_with_progress(file, &CountOptions::default(), 500, |info| {
+            updates.push((info.num_lines(), info.num_bytes()))
+        })
+        .unwrap();
+        // 500 バイトの倍数を超えた行を読み終えたところで呼ばれる
+        assert_eq!(updates, [(106, 502), (211, 1002), (316, 1501)]);
+        assert_eq!(info, count(Cursor::new(input.as_str())).unwrap());
+
+        // 途中経過を求めなくても結果は同じ
+        let mut called = false;
+        count_with_progress(Cursor::new(""), &CountOptions::default(), 1, |_| {
+            called = true
+        })
+        .unwrap();
+        assert!(!called);
+    }
+
+    #[test]
+    fn test_count_blank_lines() {
+        let info = count(Cursor::new("a\n\n \t\n b\n\r\n")).unwrap();
+        assert_eq!(info.num_blank_lines(), 3);
+        assert_eq!(info.num_nonblank_lines(), 2);
+
+        // NUL 区切りでは改行も空白として扱う
+        let options = CountOptions {
+            zero_terminated: true,
+            ..Default::default()
+        };
+        let info = count_with(Cursor::new("\n\0a\0"), &options).unwrap();
+        assert_eq!(info.num_blank_lines(), 1);
+        assert_eq!(info.num_nonblank_lines(), 1);
+    }
 }
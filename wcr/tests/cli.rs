@@ -9,6 +9,8 @@ const PRG: &str = "wcr";
 const EMPTY: &str = "tests/inputs/empty.txt";
 const FOX: &str = "tests/inputs/fox.txt";
 const ATLAMAL: &str = "tests/inputs/atlamal.txt";
+const INVALID_UTF8: &str = "tests/inputs/invalid_utf8.txt";
+const BOM: &str = "tests/inputs/bom.txt";
 
 // --------------------------------------------------
 fn gen_bad_file() -> String {
@@ -63,11 +65,25 @@ fn skips_bad_file() -> Result<()> {
     Command::cargo_bin(PRG)?
         .arg(bad)
         .assert()
-        .success()
+        .failure()
         .stderr(predicate::str::is_match(expected)?);
     Ok(())
 }
 
+// --------------------------------------------------
+#[test]
+fn bad_file_with_good() -> Result<()> {
+    let bad = gen_bad_file();
+    let expected = fs::read_to_string("tests/expected/fox.txt.out")?;
+    Command::cargo_bin(PRG)?
+        .args([&bad, FOX])
+        .assert()
+        .failure()
+        .stdout(predicate::str::starts_with(expected))
+        .stderr(predicate::str::contains(bad));
+    Ok(())
+}
+
 // --------------------------------------------------
 #[test]
 fn empty() -> Result<()> {
@@ -222,3 +238,281 @@ fn test_all_words_lines() -> Result<()> {
 fn test_all_bytes_lines() -> Result<()> {
     run(&["-cl", EMPTY, FOX, ATLAMAL], "tests/expected/all.cl.out")
 }
+
+// --------------------------------------------------
+#[test]
+fn atlamal_max_line_length() -> Result<()> {
+    run(&["-L", ATLAMAL], "tests/expected/atlamal.txt.L.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn atlamal_lines_max_line_length() -> Result<()> {
+    run(
+        &["-l", "--max-line-length", ATLAMAL],
+        "tests/expected/atlamal.txt.lL.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn test_all_max_line_length() -> Result<()> {
+    run(&["-L", EMPTY, FOX, ATLAMAL], "tests/expected/all.L.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn invalid_utf8_bytes_lines() -> Result<()> {
+    run(&["-l", "-c", INVALID_UTF8], "tests/expected/invalid_utf8.txt.cl.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn files0_from() -> Result<()> {
+    run(
+        &["--files0-from", "tests/inputs/files0.txt", FOX],
+        "tests/expected/all.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn files0_from_stdin() -> Result<()> {
+    let input = fs::read("tests/inputs/files0.txt")?;
+    let expected = fs::read_to_string("tests/expected/all.out")?;
+    Command::cargo_bin(PRG)?
+        .args(["--files0-from", "-"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_files0_from_empty_name() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--files0-from", "tests/inputs/files0_empty_name.txt"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid zero-length file name"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn zero_terminated() -> Result<()> {
+    run(&["-z", "tests/inputs/zero.txt"], "tests/expected/zero.txt.z.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn stdin_name() -> Result<()> {
+    let input = fs::read_to_string(ATLAMAL)?;
+    let expected = fs::read_to_string("tests/expected/atlamal.txt.stdin.out")?;
+    let expected = format!("{} input\n", expected.trim_end());
+
+    Command::cargo_bin(PRG)?
+        .args(["-", "--stdin-name=input"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn test_all_total_only() -> Result<()> {
+    run(
+        &["--total=only", EMPTY, FOX, ATLAMAL],
+        "tests/expected/all.total_only.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn test_all_total_never() -> Result<()> {
+    run(
+        &["--total", "never", EMPTY, FOX, ATLAMAL],
+        "tests/expected/all.total_never.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn fox_total_always() -> Result<()> {
+    run(
+        &["--total=always", FOX],
+        "tests/expected/fox.txt.total_always.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn graphemes_chars() -> Result<()> {
+    run(
+        &["-m", "tests/inputs/graphemes.txt"],
+        "tests/expected/graphemes.txt.m.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn graphemes() -> Result<()> {
+    run(
+        &["-m", "--graphemes", "tests/inputs/graphemes.txt"],
+        "tests/expected/graphemes.txt.graphemes.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn cjk_words() -> Result<()> {
+    // 空白で区切られていない日本語の文は 1 単語
+    run(&["-w", "tests/inputs/cjk.txt"], "tests/expected/cjk.txt.w.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn cjk_words_unicode() -> Result<()> {
+    run(
+        &["-w", "--words-unicode", "tests/inputs/cjk.txt"],
+        "tests/expected/cjk.txt.words_unicode.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn test_all_jobs() -> Result<()> {
+    run(&["--jobs", "2", EMPTY, FOX, ATLAMAL], "tests/expected/all.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_jobs() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--jobs", "0", FOX])
+        .assert()
+        .failure()
+        .stderr("invalid number of jobs -- 0\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn thousands() -> Result<()> {
+    let input = "x\n".repeat(617);
+    Command::cargo_bin(PRG)?
+        .arg("--thousands")
+        .write_stdin(input.clone())
+        .assert()
+        .success()
+        .stdout("     617     617   1,234\n");
+    // 指定しなければ区切らない
+    Command::cargo_bin(PRG)?
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout("     617     617    1234\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn skip_bom() -> Result<()> {
+    // 既定では GNU wc と同じく BOM も数える
+    for (flag, expected) in [("-c", 15), ("-m", 13)] {
+        Command::cargo_bin(PRG)?
+            .args([flag, BOM])
+            .assert()
+            .success()
+            .stdout(format!("{:>8} {}\n", expected, BOM));
+    }
+    for (flag, expected) in [("-c", 12), ("-m", 12)] {
+        Command::cargo_bin(PRG)?
+            .args([flag, "--skip-bom", BOM])
+            .assert()
+            .success()
+            .stdout(format!("{:>8} {}\n", expected, BOM));
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dir_and_file() -> Result<()> {
+    // ディレクトリはエラーにして、残りのファイルは数える
+    let expected = fs::read_to_string("tests/expected/fox.txt.out")?;
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", FOX])
+        .assert()
+        .failure()
+        .stderr("wcr: tests/inputs: Is a directory\n")
+        .stdout(predicate::str::starts_with(expected));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn progress_stdin() -> Result<()> {
+    // 1 MB ごとに途中経過を標準エラー出力に表示する
+    let input = "a\n".repeat(1024 * 1024);
+    Command::cargo_bin(PRG)?
+        .arg("--progress=1")
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout(" 1048576 1048576 2097152\n")
+        .stderr(
+            "wcr: -: 524288 lines, 1048576 bytes so far\n\
+             wcr: -: 1048576 lines, 2097152 bytes so far\n",
+        );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_progress() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .arg("--progress=0")
+        .write_stdin("")
+        .assert()
+        .failure()
+        .stderr("invalid progress interval -- 0\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn blank_lines() -> Result<()> {
+    // 空の行と空白だけの行を数える
+    run(
+        &["--blank-lines", "tests/inputs/blank.txt"],
+        "tests/expected/blank.txt.blank_lines.out",
+    )
+}
+
+#[test]
+fn nonblank_lines() -> Result<()> {
+    run(
+        &["--nonblank-lines", "tests/inputs/blank.txt"],
+        "tests/expected/blank.txt.nonblank_lines.out",
+    )
+}
+
+#[test]
+fn blank_nonblank_total() -> Result<()> {
+    // 合計は各ファイルの値を足したもの
+    run(
+        &[
+            "-l",
+            "--blank-lines",
+            "--nonblank-lines",
+            "tests/inputs/blank.txt",
+            FOX,
+        ],
+        "tests/expected/blank_fox.blank_nonblank.out",
+    )
+}